@@ -1,7 +1,9 @@
 pub mod beamsearch_solver;
+pub mod frontier;
+pub mod observer;
 
 mod beamsearch_collection;
 mod parent_tree;
 mod mocks;
 
-pub use beamsearch_solver::Params;
\ No newline at end of file
+pub use beamsearch_solver::{Params, ParamsError};
\ No newline at end of file
@@ -1,9 +1,16 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use log::debug;
 
 use super::beamsearch_solver::Node;
+use rand::Rng;
 use rayon::prelude::*;
 
+/// Collections and similarity groups smaller than this are processed on the current thread, since the rayon
+/// overhead outweighs the gain for them.
+pub const PARALLEL_SIMILARITY_THRESHOLD: usize = 1024;
+
 pub trait BeamsearchNode {
     fn fitness(&self) -> f64;
     fn level(&self) -> f64;
@@ -46,10 +53,14 @@ where
         self.sorted = false;
     }
 
-    fn inner_sort(to_sort: &mut Vec<Node<T>>) {
+    fn inner_sort(to_sort: &mut [Node<T>]) {
         to_sort.sort_by(|a, b| a.data().fitness().total_cmp(&b.data().fitness()));
     }
 
+    fn inner_par_sort(to_sort: &mut [Node<T>]) {
+        to_sort.par_sort_by(|a, b| a.data().fitness().total_cmp(&b.data().fitness()));
+    }
+
     pub fn sort(&mut self) {
         if self.sorted {
             return;
@@ -59,18 +70,112 @@ where
         self.sorted = true;
     }
 
+    /// Keeps the `target_size` nodes with the lowest fitness. Unless the collection was sorted before, the
+    /// survivors are only selected in linear time and keep their previous order; call `sort` if it matters.
+    /// Among nodes tied at the cut, the ones added first survive, as with a stable sort.
     pub fn keep_best(&mut self, target_size: usize) -> usize {
         if target_size >= self.len() {
             return 0;
         }
 
-        self.sort();
-
         let deleted = self.len() - target_size;
-        self.nodes.truncate(target_size);
+        if self.sorted {
+            self.nodes.truncate(target_size);
+            return deleted;
+        }
+        if target_size == 0 {
+            self.nodes.clear();
+            return deleted;
+        }
+
+        let mut fitnesses: Vec<f64> = self.nodes.iter().map(|n| n.data().fitness()).collect();
+        let (better, &mut cut, _) =
+            fitnesses.select_nth_unstable_by(target_size - 1, f64::total_cmp);
+        // the cut itself and the nodes tied with it in front of it are kept as well
+        let mut ties_left =
+            target_size - better.iter().filter(|f| f.total_cmp(&cut).is_lt()).count();
+
+        self.nodes
+            .retain(|node| match node.data().fitness().total_cmp(&cut) {
+                Ordering::Less => true,
+                Ordering::Equal if ties_left > 0 => {
+                    ties_left -= 1;
+                    true
+                }
+                _ => false,
+            });
         deleted
     }
 
+    /// Randomly keeps `target_size` nodes, each drawn with a probability proportional to
+    /// exp(-fitness / temperature) among the nodes not drawn yet. The lower the temperature, the more it
+    /// resembles `keep_best`, which it falls back to for a temperature of 0.
+    pub fn keep_best_stochastic<R: Rng>(
+        &mut self,
+        target_size: usize,
+        rng: &mut R,
+        temperature: f64,
+    ) -> usize {
+        if target_size >= self.len() {
+            return 0;
+        }
+        if temperature <= 0.0 {
+            return self.keep_best(target_size);
+        }
+
+        // Gumbel-top-k: perturbing the scaled fitnesses by Gumbel noise and keeping the smallest ones
+        // draws without replacement from the softmax distribution
+        let mut keyed: Vec<(f64, Node<T>)> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(|node| {
+                let gumbel = -(-rng.random::<f64>().ln()).ln();
+                (node.data().fitness() / temperature - gumbel, node)
+            })
+            .collect();
+        keyed.select_nth_unstable_by(target_size, |a, b| a.0.total_cmp(&b.0));
+
+        let deleted = keyed.len() - target_size;
+        keyed.truncate(target_size);
+        self.nodes = keyed.into_iter().map(|(_, node)| node).collect();
+        self.sorted = false;
+        self.sort();
+        deleted
+    }
+
+    /// Keeps the best `target_size` nodes plus up to `diversity_size` of the remaining ones,
+    /// taking the best node of each similarity hash not already present among the survivors.
+    pub fn keep_best_and_diverse<SHash>(
+        &mut self,
+        target_size: usize,
+        diversity_size: usize,
+        similarity_hash: SHash,
+    ) -> usize
+    where
+        SHash: Fn(&Node<T>) -> u64,
+    {
+        if target_size >= self.len() {
+            return 0;
+        }
+
+        self.sort();
+
+        let size_before = self.len();
+        let rest = self.nodes.split_off(target_size);
+        let mut seen_hashes: HashSet<u64> = self.nodes.iter().map(&similarity_hash).collect();
+
+        // rest is sorted and only contains nodes not better than the kept ones, so order is preserved
+        for node in rest {
+            if self.nodes.len() == target_size + diversity_size {
+                break;
+            }
+            if seen_hashes.insert(similarity_hash(&node)) {
+                self.nodes.push(node);
+            }
+        }
+
+        size_before - self.len()
+    }
+
     pub fn get_best(&self) -> Option<&Node<T>> {
         if self.sorted {
             return self.nodes.first();
@@ -80,30 +185,115 @@ where
             .min_by(|a, b| a.data().fitness().total_cmp(&b.data().fitness()))
     }
 
+    /// Removes all nodes with a fitness above the given one, returning their number.
+    pub fn remove_worse_than(&mut self, fitness: f64) -> usize {
+        let size_before = self.len();
+        self.nodes
+            .retain(|node| node.data().fitness().total_cmp(&fitness).is_le());
+        size_before - self.len()
+    }
+
+    /// Splits into the nodes matching the predicate and the rest, keeping their relative order.
+    pub fn partition<P>(self, pred: P) -> (Self, Self)
+    where
+        P: Fn(&Node<T>) -> bool,
+    {
+        let (matching, rest): (Vec<_>, Vec<_>) = self.nodes.into_iter().partition(|n| pred(n));
+        (
+            Self {
+                nodes: matching,
+                sorted: self.sorted,
+            },
+            Self {
+                nodes: rest,
+                sorted: self.sorted,
+            },
+        )
+    }
+
     pub fn remove_similars<S, SHash>(&mut self, is_similar: S, similarity_hash: SHash) -> usize
     where
         S: Fn(&Node<T>, &Node<T>) -> bool + Send + Sync,
-        SHash: Fn(&Node<T>) -> u32,
+        SHash: Fn(&Node<T>) -> u64,
     {
-        let size_before = self.nodes.len();
+        self.remove_similars_within(is_similar, similarity_hash, None)
+            .len()
+    }
 
-        let old_nodes = std::mem::take(&mut self.nodes);
-        let similarity_groups = Self::create_similarity_groups_from(old_nodes, similarity_hash);
+    /// Like `remove_similars`, but groups that are not started within the time budget are kept as they are.
+    /// The largest groups are started first, since they are likely to contain the most similar nodes.
+    /// Returns the removed nodes.
+    pub fn remove_similars_within<S, SHash>(
+        &mut self,
+        is_similar: S,
+        similarity_hash: SHash,
+        time_budget: Option<Duration>,
+    ) -> Vec<Node<T>>
+    where
+        S: Fn(&Node<T>, &Node<T>) -> bool + Send + Sync,
+        SHash: Fn(&Node<T>) -> u64,
+    {
+        self.remove_similars_with_threshold(
+            is_similar,
+            similarity_hash,
+            time_budget,
+            PARALLEL_SIMILARITY_THRESHOLD,
+        )
+    }
 
-        self.nodes = similarity_groups
-            .into_par_iter()
-            .map(|(_, group)| Self::remove_similars_for(group, &is_similar))
-            .flatten()
-            .collect();
+    fn remove_similars_with_threshold<S, SHash>(
+        &mut self,
+        is_similar: S,
+        similarity_hash: SHash,
+        time_budget: Option<Duration>,
+        parallel_threshold: usize,
+    ) -> Vec<Node<T>>
+    where
+        S: Fn(&Node<T>, &Node<T>) -> bool + Send + Sync,
+        SHash: Fn(&Node<T>) -> u64,
+    {
+        let size_before = self.nodes.len();
+        let deadline = time_budget.map(|budget| Instant::now() + budget);
 
-        size_before - self.nodes.len()
+        let old_nodes = std::mem::take(&mut self.nodes);
+        let mut similarity_groups: Vec<Vec<Node<T>>> =
+            Self::create_similarity_groups_from(old_nodes, similarity_hash)
+                .into_values()
+                .collect();
+        similarity_groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+
+        let process = |group: Vec<Node<T>>| match deadline {
+            Some(deadline) if Instant::now() >= deadline => (group, Vec::new()),
+            _ => Self::remove_similars_for(group, &is_similar, parallel_threshold),
+        };
+        let processed: Vec<_> = if size_before < parallel_threshold {
+            similarity_groups.into_iter().map(process).collect()
+        } else {
+            similarity_groups.into_par_iter().map(process).collect()
+        };
+
+        let mut removed = Vec::new();
+        for (kept_in_group, removed_in_group) in processed {
+            self.nodes.extend(kept_in_group);
+            removed.extend(removed_in_group);
+        }
+        removed
     }
 
-    fn remove_similars_for<S>(mut group: Vec<Node<T>>, is_similar: S) -> Vec<Node<T>>
+    // Splits the group into the nodes to keep and the ones similar to a better node.
+    fn remove_similars_for<S>(
+        mut group: Vec<Node<T>>,
+        is_similar: S,
+        parallel_threshold: usize,
+    ) -> (Vec<Node<T>>, Vec<Node<T>>)
     where
         S: Fn(&Node<T>, &Node<T>) -> bool,
     {
-        Self::inner_sort(&mut group);
+        if group.len() < parallel_threshold {
+            Self::inner_sort(&mut group);
+        } else {
+            Self::inner_par_sort(&mut group);
+        }
 
         let mut keep_mask = vec![true; group.len()];
 
@@ -125,23 +315,24 @@ where
             }
         }
 
+        let mut removed = Vec::new();
         for i in (0..group.len()).rev() {
             if !keep_mask[i] {
-                group.swap_remove(i);
+                removed.push(group.swap_remove(i));
             }
         }
-        group
+        (group, removed)
     }
 
     fn create_similarity_groups_from<SHash>(
         nodes: Vec<Node<T>>,
         similarity_hash: SHash,
-    ) -> HashMap<u32, Vec<Node<T>>>
+    ) -> HashMap<u64, Vec<Node<T>>>
     where
         T: BeamsearchNode + Send + Sync,
-        SHash: Fn(&Node<T>) -> u32,
+        SHash: Fn(&Node<T>) -> u64,
     {
-        let mut similarity_groups = HashMap::<u32, Vec<Node<T>>>::new();
+        let mut similarity_groups = HashMap::<u64, Vec<Node<T>>>::new();
         for node in nodes {
             let hash = similarity_hash(&node);
             similarity_groups.entry(hash).or_default().push(node);
@@ -263,6 +454,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_keep_best_stochastic_without_temperature_equals_keep_best() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut deterministic = create_test_collection(20);
+        deterministic.keep_best(5);
+        deterministic.sort();
+
+        for temperature in [0.0, 1e-9] {
+            let mut stochastic = create_test_collection(20);
+
+            let deleted = stochastic.keep_best_stochastic(5, &mut rng, temperature);
+            stochastic.sort();
+
+            assert_eq!(deleted, 15);
+            let fitnesses = |coll: &BeamsearchCollection<TestNode>| -> Vec<f64> {
+                coll.iter().map(|node| node.data().fitness()).collect()
+            };
+            assert_eq!(fitnesses(&stochastic), fitnesses(&deterministic));
+        }
+    }
+
+    #[test]
+    fn test_keep_best_stochastic_keeps_worse_nodes_at_high_temperature() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut deterministic = create_test_collection(20);
+        deterministic.keep_best(5);
+        let worst_kept = deterministic.iter().last().unwrap().data().fitness();
+
+        let mut stochastic = create_test_collection(20);
+        stochastic.keep_best_stochastic(5, &mut rng, 1000.0);
+
+        assert_eq!(stochastic.len(), 5);
+        assert!(
+            stochastic
+                .iter()
+                .any(|node| node.data().fitness() > worst_kept)
+        );
+    }
+
     #[test]
     fn test_keep_best() {
         let mut coll = create_test_collection(10);
@@ -277,12 +507,31 @@ mod tests {
         fitnesses.truncate(5);
 
         coll.keep_best(5);
+        coll.sort();
 
         for (node, expected_fitness) in zip(&coll, fitnesses) {
             assert_eq!(node.data().fitness(), expected_fitness);
         }
     }
 
+    #[test]
+    fn test_keep_best_keeps_first_added_of_tied_nodes_in_order() {
+        let mut coll = BeamsearchCollection::<TestNode>::default();
+        let fitnesses_and_levels = [(3.0, 0.0), (1.0, 1.0), (2.0, 2.0), (1.0, 3.0), (2.0, 4.0)];
+        for (dummy_fitness, dummy_level) in fitnesses_and_levels {
+            coll.add(Node::new_root(TestNode {
+                dummy_fitness,
+                dummy_level,
+            }));
+        }
+
+        let deleted = coll.keep_best(3);
+
+        assert_eq!(deleted, 2);
+        let levels: Vec<f64> = coll.iter().map(|n| n.data().level()).collect();
+        assert_eq!(levels, vec![1.0, 2.0, 3.0]);
+    }
+
     #[test]
     fn test_get_best() {
         let coll = create_test_collection(10);
@@ -326,13 +575,15 @@ mod tests {
         });
 
         for i in 0..10 {
-            let mut child = TestNode::default();
-            child.dummy_fitness = i as f64;
-            coll_rising_fitness.add(root.new_child(child));
+            coll_rising_fitness.add(root.new_child(TestNode {
+                dummy_fitness: i as f64,
+                ..Default::default()
+            }));
 
-            let mut child = TestNode::default();
-            child.dummy_fitness = -i as f64;
-            coll_decreasing_fitness.add(root.new_child(child));
+            coll_decreasing_fitness.add(root.new_child(TestNode {
+                dummy_fitness: -i as f64,
+                ..Default::default()
+            }));
         }
 
         assert_eq!(coll_rising_fitness.len(), 10);
@@ -341,11 +592,11 @@ mod tests {
         //all are equal because all share the same level
         coll_rising_fitness.remove_similars(
             |a, b| a.data().level() == b.data().level(),
-            |n| n.data().level() as u32,
+            |n| n.data().level() as u64,
         );
         coll_decreasing_fitness.remove_similars(
             |a, b| a.data().level() == b.data().level(),
-            |n| n.data().level() as u32,
+            |n| n.data().level() as u64,
         );
 
         assert_eq!(coll_rising_fitness.len(), 1);
@@ -354,4 +605,128 @@ mod tests {
         assert_eq!(coll_rising_fitness.nodes[0].data().fitness(), 0.0);
         assert_eq!(coll_decreasing_fitness.nodes[0].data().fitness(), -9.0);
     }
+
+    // nine out of ten nodes share a single hash, the rest are spread over their own hashes
+    fn create_skewed_collection(size: usize) -> BeamsearchCollection<TestNode> {
+        let mut coll = create_test_collection(size);
+        for (i, node) in coll.nodes.iter_mut().enumerate() {
+            let level = if i % 10 == 0 {
+                1000.0 + i as f64
+            } else {
+                (i % 100) as f64
+            };
+            *node = node.parent().unwrap().new_child(TestNode {
+                dummy_fitness: node.data().fitness(),
+                dummy_level: level,
+            });
+        }
+        coll
+    }
+
+    fn skewed_hash(node: &Node<TestNode>) -> u64 {
+        if node.data().level() >= 1000.0 {
+            node.data().level() as u64
+        } else {
+            0
+        }
+    }
+
+    #[test]
+    fn test_remove_similars_is_independent_of_parallel_threshold() {
+        let fitnesses = |threshold: usize| -> Vec<f64> {
+            let mut coll = create_skewed_collection(2000);
+            coll.remove_similars_with_threshold(
+                |a, b| a.data().level() == b.data().level(),
+                skewed_hash,
+                None,
+                threshold,
+            );
+            coll.sort();
+            coll.iter().map(|n| n.data().fitness()).collect()
+        };
+
+        let sequential = fitnesses(usize::MAX);
+
+        assert_eq!(sequential.len(), 200 + 90);
+        assert_eq!(fitnesses(0), sequential);
+        assert_eq!(fitnesses(PARALLEL_SIMILARITY_THRESHOLD), sequential);
+    }
+
+    // run with `cargo test --release bench_remove_similars -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_remove_similars_on_skewed_hashes() {
+        for size in [500, 5_000, 20_000] {
+            for (name, threshold) in [
+                ("sequential", usize::MAX),
+                ("always parallel", 0),
+                ("threshold", PARALLEL_SIMILARITY_THRESHOLD),
+            ] {
+                let mut coll = create_skewed_collection(size);
+                let start = Instant::now();
+                coll.remove_similars_with_threshold(
+                    |a, b| a.data().level() == b.data().level(),
+                    skewed_hash,
+                    None,
+                    threshold,
+                );
+                println!("{size:>6} nodes, {name:>15}: {:?}", start.elapsed());
+            }
+        }
+    }
+
+    #[test]
+    fn test_keep_best_and_diverse_retains_more_distinct_hashes() {
+        let create_coll = || {
+            let root = Node::new_root(TestNode::default());
+            let mut coll = BeamsearchCollection::<TestNode>::default();
+            // the four best nodes share level 0, the others each have their own level
+            for i in 0..8 {
+                coll.add(root.new_child(TestNode {
+                    dummy_fitness: i as f64,
+                    dummy_level: if i < 4 { 0.0 } else { i as f64 },
+                }));
+            }
+            coll
+        };
+        let count_distinct_levels = |coll: &BeamsearchCollection<TestNode>| {
+            coll.iter()
+                .map(|n| n.data().level() as u64)
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        let mut plain = create_coll();
+        plain.keep_best(2);
+
+        let mut diverse = create_coll();
+        let deleted = diverse.keep_best_and_diverse(2, 2, |n| n.data().level() as u64);
+
+        assert_eq!(count_distinct_levels(&plain), 1);
+        assert_eq!(count_distinct_levels(&diverse), 3);
+        assert_eq!(deleted, 4);
+        assert_eq!(diverse.get_best().unwrap().data().fitness(), 0.0);
+        let fitnesses: Vec<f64> = diverse.iter().map(|n| n.data().fitness()).collect();
+        assert_eq!(fitnesses, vec![0.0, 1.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_partition_keeps_all_nodes() {
+        let mut coll = create_test_collection(20);
+        coll.sort();
+
+        let (low, high) = coll.partition(|n| n.data().level() < 50.0);
+
+        assert_eq!(low.len() + high.len(), 20);
+        assert!(low.iter().all(|n| n.data().level() < 50.0));
+        assert!(high.iter().all(|n| n.data().level() >= 50.0));
+        assert!(low.sorted && high.sorted);
+        assert_eq!(
+            low.get_best().unwrap().data().fitness(),
+            low.iter()
+                .map(|n| n.data().fitness())
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap()
+        );
+    }
 }
@@ -1,15 +1,359 @@
 use super::beamsearch_collection::BeamsearchCollection;
 pub use super::beamsearch_collection::BeamsearchNode;
+use super::frontier::{BeamFrontier, Frontier};
+use super::observer::{IterationEvent, NoObserver, ProgressStats, SolverObserver};
 use super::parent_tree::ParentTreeNode;
+use rand::{SeedableRng, rngs::StdRng};
 use rayon::prelude::*;
-use std::time::Instant;
-use log::{info, debug};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use log::{info, trace};
 
 pub type Node<T> = ParentTreeNode<T>;
 
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Params {
     pub beam_width: usize,
     pub prune_similars: bool,
+    /// What a complete tour is judged by; penalties are added on top either way.
+    pub objective: Objective,
+    /// Which nodes count as similar when `prune_similars` is set.
+    pub similarity_mode: SimilarityMode,
+    /// Nodes at the same position whose times differ by less than this count as similar in
+    /// `SimilarityMode::ArrivalTime`. Larger values prune more aggressively at the cost of possibly discarding
+    /// the optimum; the right scale depends on the instance.
+    pub similarity_time_epsilon: f64,
+    /// Number of additional survivors per iteration picked from the nodes beyond `beam_width`,
+    /// preferring nodes whose similarity hash is not yet represented. 0 disables the diversity beam.
+    pub diversity_beam_width: usize,
+    /// Number of levels a candidate is looked ahead to check that it is not a dead end. 0 disables the look-ahead.
+    pub lookahead_depth: usize,
+    /// Whether the edge returning to the depot counts towards the objective. The tour is closed either way.
+    pub include_return_cost: bool,
+    /// If set, every node keeps only this many randomly sampled feasible children.
+    pub random_candidates: Option<usize>,
+    /// If set, a node is only expanded towards the feasible cities among this many nearest neighbors of its city,
+    /// or towards all feasible cities if none of them is. The return to the depot is always allowed. This trades
+    /// the guarantee of finding the optimum for speed on large instances.
+    pub candidate_list_size: Option<usize>,
+    /// Seed for all random decisions, so that runs with equal seeds are reproducible.
+    pub seed: u64,
+    /// Penalty added to the objective for every stop at which the vehicle has to wait for the window to open.
+    pub wait_count_weight: f64,
+    /// Penalty added to the objective for every optional city a complete tour leaves out.
+    pub skip_penalty: f64,
+    /// If set, windows may be closed on arrival; every time unit of lateness adds this penalty to the objective.
+    pub lateness_penalty: Option<f64>,
+    /// If set, the incumbent is written to the file whenever the trigger fires.
+    pub checkpoint: Option<(PathBuf, CheckpointTrigger)>,
+    /// If set, the search stops at the first iteration starting after this much time and returns the best
+    /// valid solution found so far, which is None if no valid solution was reached before the deadline.
+    pub time_limit: Option<Duration>,
+    /// If set, the search stops like on reaching the time limit once the flag is set, e.g. from another thread.
+    /// Cancellation is cooperative and only observed between iterations.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Soft limit on the time spent removing similar nodes per iteration; groups not started in time are skipped.
+    pub similar_time_budget: Option<Duration>,
+    /// Whether the objective adds a lower bound on the distance still needed to complete the tour.
+    pub completion_lower_bound: bool,
+    /// Number of best valid solutions kept aside from the beam, so that they cannot be truncated away.
+    pub archive_size: usize,
+    /// Number of additional searches that break ties differently, the best solution over all searches is kept.
+    pub restarts: usize,
+    /// If set, the survivors of every iteration are drawn randomly, preferring better nodes the lower this
+    /// temperature is. Ignored if the diversity beam is enabled.
+    pub selection_temperature: Option<f64>,
+    /// If set and no valid solution is found, the beam width is doubled up to this width until one is found.
+    pub auto_widen: Option<usize>,
+    /// Whether nodes worse than the best valid solution found so far are dropped. Only sound if the fitness
+    /// of a node never decreases from a node to its children, hence disabled by default. The cost of a partial
+    /// tour in `solve_tsp` never decreases, so it can be enabled there.
+    pub prune_by_incumbent: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            beam_width: 100,
+            prune_similars: true,
+            objective: Objective::default(),
+            similarity_mode: SimilarityMode::default(),
+            similarity_time_epsilon: 1.0,
+            diversity_beam_width: 0,
+            lookahead_depth: 0,
+            include_return_cost: true,
+            random_candidates: None,
+            candidate_list_size: None,
+            seed: 0,
+            wait_count_weight: 0.0,
+            skip_penalty: 0.0,
+            lateness_penalty: None,
+            checkpoint: None,
+            time_limit: None,
+            cancel: None,
+            similar_time_budget: None,
+            completion_lower_bound: false,
+            archive_size: 1,
+            restarts: 0,
+            auto_widen: None,
+            selection_temperature: None,
+            prune_by_incumbent: false,
+        }
+    }
+}
+
+impl Params {
+    pub fn builder() -> ParamsBuilder {
+        ParamsBuilder::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamsError {
+    ZeroBeamWidth,
+    /// Without room in the archive, no solution could be returned.
+    ZeroArchiveSize,
+    /// A penalty, the similarity epsilon or the selection temperature is negative or NaN.
+    Negative {
+        field: &'static str,
+        value: f64,
+    },
+}
+
+impl std::fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamsError::ZeroBeamWidth => write!(f, "beam width must be positive"),
+            ParamsError::ZeroArchiveSize => write!(f, "archive size must be positive"),
+            ParamsError::Negative { field, value } => {
+                write!(f, "{field} must be a nonnegative number, got {value}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+/// Builds `Params` starting from the defaults, checking the values in `build`.
+#[derive(Clone, Default)]
+pub struct ParamsBuilder {
+    params: Params,
+}
+
+impl ParamsBuilder {
+    pub fn beam_width(mut self, beam_width: usize) -> Self {
+        self.params.beam_width = beam_width;
+        self
+    }
+
+    pub fn prune_similars(mut self, prune_similars: bool) -> Self {
+        self.params.prune_similars = prune_similars;
+        self
+    }
+
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.params.objective = objective;
+        self
+    }
+
+    pub fn similarity_mode(mut self, similarity_mode: SimilarityMode) -> Self {
+        self.params.similarity_mode = similarity_mode;
+        self
+    }
+
+    pub fn similarity_time_epsilon(mut self, similarity_time_epsilon: f64) -> Self {
+        self.params.similarity_time_epsilon = similarity_time_epsilon;
+        self
+    }
+
+    pub fn diversity_beam_width(mut self, diversity_beam_width: usize) -> Self {
+        self.params.diversity_beam_width = diversity_beam_width;
+        self
+    }
+
+    pub fn lookahead_depth(mut self, lookahead_depth: usize) -> Self {
+        self.params.lookahead_depth = lookahead_depth;
+        self
+    }
+
+    pub fn include_return_cost(mut self, include_return_cost: bool) -> Self {
+        self.params.include_return_cost = include_return_cost;
+        self
+    }
+
+    pub fn random_candidates(mut self, random_candidates: usize) -> Self {
+        self.params.random_candidates = Some(random_candidates);
+        self
+    }
+
+    pub fn candidate_list_size(mut self, candidate_list_size: usize) -> Self {
+        self.params.candidate_list_size = Some(candidate_list_size);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.params.seed = seed;
+        self
+    }
+
+    pub fn wait_count_weight(mut self, wait_count_weight: f64) -> Self {
+        self.params.wait_count_weight = wait_count_weight;
+        self
+    }
+
+    pub fn skip_penalty(mut self, skip_penalty: f64) -> Self {
+        self.params.skip_penalty = skip_penalty;
+        self
+    }
+
+    pub fn lateness_penalty(mut self, lateness_penalty: f64) -> Self {
+        self.params.lateness_penalty = Some(lateness_penalty);
+        self
+    }
+
+    pub fn checkpoint(mut self, path: PathBuf, trigger: CheckpointTrigger) -> Self {
+        self.params.checkpoint = Some((path, trigger));
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.params.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.params.cancel = Some(cancel);
+        self
+    }
+
+    pub fn similar_time_budget(mut self, similar_time_budget: Duration) -> Self {
+        self.params.similar_time_budget = Some(similar_time_budget);
+        self
+    }
+
+    pub fn completion_lower_bound(mut self, completion_lower_bound: bool) -> Self {
+        self.params.completion_lower_bound = completion_lower_bound;
+        self
+    }
+
+    pub fn archive_size(mut self, archive_size: usize) -> Self {
+        self.params.archive_size = archive_size;
+        self
+    }
+
+    pub fn restarts(mut self, restarts: usize) -> Self {
+        self.params.restarts = restarts;
+        self
+    }
+
+    pub fn selection_temperature(mut self, selection_temperature: f64) -> Self {
+        self.params.selection_temperature = Some(selection_temperature);
+        self
+    }
+
+    pub fn auto_widen(mut self, auto_widen: usize) -> Self {
+        self.params.auto_widen = Some(auto_widen);
+        self
+    }
+
+    pub fn prune_by_incumbent(mut self, prune_by_incumbent: bool) -> Self {
+        self.params.prune_by_incumbent = prune_by_incumbent;
+        self
+    }
+
+    pub fn build(self) -> Result<Params, ParamsError> {
+        let params = self.params;
+        if params.beam_width == 0 {
+            return Err(ParamsError::ZeroBeamWidth);
+        }
+        if params.archive_size == 0 {
+            return Err(ParamsError::ZeroArchiveSize);
+        }
+        let nonnegative = [
+            (
+                "similarity_time_epsilon",
+                Some(params.similarity_time_epsilon),
+            ),
+            ("wait_count_weight", Some(params.wait_count_weight)),
+            ("skip_penalty", Some(params.skip_penalty)),
+            ("lateness_penalty", params.lateness_penalty),
+            ("selection_temperature", params.selection_temperature),
+        ];
+        for (field, value) in nonnegative {
+            if let Some(value) = value
+                && (value.is_nan() || value < 0.0)
+            {
+                return Err(ParamsError::Negative { field, value });
+            }
+        }
+        Ok(params)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Objective {
+    /// Total travel distance.
+    #[default]
+    MinDistance,
+    /// Time at which the tour is completed, including waiting and service times.
+    MinMakespan,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SimilarityMode {
+    /// Nodes at the same position with nearly the same time are similar, the worse one is removed.
+    #[default]
+    ArrivalTime,
+    /// A node is removed if another node at the same position is at least as good in every criterion.
+    Dominance,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckpointTrigger {
+    /// After every n-th iteration.
+    Iterations(usize),
+    /// At the first iteration after this much time passed since the last checkpoint.
+    Interval(Duration),
+}
+
+/// Counters and accumulated phase timings of a search.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolverStats {
+    pub expand_time: Duration,
+    pub similar_removal_time: Duration,
+    pub keep_best_time: Duration,
+    /// Number of iterations that expanded at least one node.
+    pub iterations: usize,
+    pub similars_removed: usize,
+    /// Number of nodes dropped for being worse than the best valid solution found so far.
+    pub pruned_by_incumbent: usize,
+    /// Largest size of the collection, reached right after an expansion.
+    pub peak_collection_size: usize,
+}
+
+impl SolverStats {
+    fn combine(self, other: Self) -> Self {
+        SolverStats {
+            expand_time: self.expand_time + other.expand_time,
+            similar_removal_time: self.similar_removal_time + other.similar_removal_time,
+            keep_best_time: self.keep_best_time + other.keep_best_time,
+            iterations: self.iterations + other.iterations,
+            similars_removed: self.similars_removed + other.similars_removed,
+            pruned_by_incumbent: self.pruned_by_incumbent + other.pruned_by_incumbent,
+            peak_collection_size: self.peak_collection_size.max(other.peak_collection_size),
+        }
+    }
 }
 
 pub struct SolverResult<T>
@@ -18,39 +362,93 @@ where
 {
     pub best: Option<Node<T>>,
     pub nr_expansions: usize,
+    /// The best valid solutions seen during the whole search, sorted by fitness.
+    pub archive: Vec<Node<T>>,
+    /// The final collection sorted by fitness, including nodes that are no valid solution.
+    pub frontier: Vec<Node<T>>,
+    pub stats: SolverStats,
+}
+
+impl<T> SolverResult<T>
+where
+    T: BeamsearchNode,
+{
+    /// Combines the results of two searches of the same problem, e.g. of restarts. The frontier is taken from the
+    /// search that found the better solution.
+    pub fn merge(self, other: Self, archive_size: usize) -> Self {
+        let fitness = |best: &Option<Node<T>>| {
+            best.as_ref()
+                .map_or(f64::INFINITY, |node| node.data().fitness())
+        };
+        let (better, worse) = if fitness(&other.best) < fitness(&self.best) {
+            (other, self)
+        } else {
+            (self, other)
+        };
+
+        let mut archive = better.archive;
+        archive.extend(worse.archive);
+        archive.sort_by(|a, b| a.data().fitness().total_cmp(&b.data().fitness()));
+        archive.truncate(archive_size);
+
+        SolverResult {
+            best: better.best,
+            nr_expansions: better.nr_expansions + worse.nr_expansions,
+            archive,
+            frontier: better.frontier,
+            stats: better.stats.combine(worse.stats),
+        }
+    }
 }
 
 pub fn is_never_similar<T>(_a: &Node<T>, _b: &Node<T>) -> bool {
     false
 }
 
-pub struct BeamsearchSolver<T, F, S, SHash, V>
+type ProgressCallback = Box<dyn FnMut(&ProgressStats)>;
+
+/// Each iteration expands the nodes taken from the frontier, and pushes the children that survive pruning back into
+/// it. With the default `BeamFrontier` this is a beam search; with a `PriorityFrontier` the nodes that are not
+/// taken stay open for later iterations, which makes it a best-first search expanding `beam_width` nodes at once.
+pub struct BeamsearchSolver<T, F, S, SHash, V, Fr = BeamFrontier<T>>
 where
     T: BeamsearchNode + Send + Sync,
-    F: Fn(&Node<T>) -> Vec<T>,
     S: Fn(&Node<T>, &Node<T>) -> bool,
-    SHash: Fn(&Node<T>) -> u32,
+    SHash: Fn(&Node<T>) -> u64,
     V: Fn(&Node<T>) -> bool,
+    Fr: Frontier<T>,
 {
+    // the nodes to expand in the current iteration
     coll: BeamsearchCollection<T>,
+    frontier: Fr,
+    archive: BeamsearchCollection<T>,
     expander: F,
     is_similar: S,
     similarity_hash: SHash,
     is_valid_solution: V,
     params: Params,
+    progress: Option<ProgressCallback>,
 }
 
-impl<T, F, S, SHash, V> BeamsearchSolver<T, F, S, SHash, V>
+impl<T, F, I, S, SHash, V> BeamsearchSolver<T, F, S, SHash, V>
 where
     T: BeamsearchNode + Send + Sync,
-    F: Fn(&Node<T>) -> Vec<T> + Send + Sync,
+    F: Fn(&Node<T>) -> I + Send + Sync,
+    I: IntoIterator<Item = T>,
     S: Fn(&Node<T>, &Node<T>) -> bool + Send + Sync,
-    SHash: Fn(&Node<T>) -> u32,
+    SHash: Fn(&Node<T>) -> u64,
     V: Fn(&Node<T>) -> bool,
 {
+    /// expander: function that returns the children of a node, e.g. as a `Vec` or as a lazy iterator that is
+    /// consumed right away.
+    ///
     /// is_similar: function that returns true if two nodes are similar (and thus one can be pruned)
     ///
     /// similarity_hash: function that returns a hash value for a node, such that only nodes with the same hash value can be similar. This is mainly to reduce calculation time. Attention, if wrongly specified, it may lead to similar nodes not being pruned. If in doubt, use a function that always returns the same value.
+    ///
+    /// is_valid_solution: function that returns true if a node is a complete and valid solution. Such nodes are archived as soon as they are created.
+    ///
+    /// The solver uses a `BeamFrontier` keeping the `beam_width` best nodes, see `with_frontier` to replace it.
     pub fn new(
         start_nodes: Vec<T>,
         expander: F,
@@ -64,50 +462,169 @@ where
             coll.add(Node::new_root(node));
         }
 
-        Self {
+        let mut solver = Self {
             coll,
+            // the diversity beam selects its additional survivors before they reach the frontier
+            frontier: BeamFrontier::new(params.beam_width + params.diversity_beam_width),
+            archive: BeamsearchCollection::default(),
             expander,
             is_similar,
             similarity_hash,
             is_valid_solution,
             params,
+            progress: None,
+        };
+        solver.archive_valid_solutions();
+        solver
+    }
+}
+
+impl<T, F, I, S, SHash, V, Fr> BeamsearchSolver<T, F, S, SHash, V, Fr>
+where
+    T: BeamsearchNode + Send + Sync,
+    F: Fn(&Node<T>) -> I + Send + Sync,
+    I: IntoIterator<Item = T>,
+    S: Fn(&Node<T>, &Node<T>) -> bool + Send + Sync,
+    SHash: Fn(&Node<T>) -> u64,
+    V: Fn(&Node<T>) -> bool,
+    Fr: Frontier<T>,
+{
+    /// Replaces the frontier the children are pushed into. Every iteration takes up to `beam_width` nodes from it.
+    pub fn with_frontier<Fr2>(self, frontier: Fr2) -> BeamsearchSolver<T, F, S, SHash, V, Fr2>
+    where
+        Fr2: Frontier<T>,
+    {
+        BeamsearchSolver {
+            coll: self.coll,
+            frontier,
+            archive: self.archive,
+            expander: self.expander,
+            is_similar: self.is_similar,
+            similarity_hash: self.similarity_hash,
+            is_valid_solution: self.is_valid_solution,
+            params: self.params,
+            progress: self.progress,
         }
     }
 
-    pub fn solve(mut self) -> SolverResult<T> {
+    /// Calls the callback once per iteration that expanded at least one node.
+    pub fn with_progress(mut self, callback: impl FnMut(&ProgressStats) + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn solve(self) -> SolverResult<T> {
+        self.solve_with_observer(&mut NoObserver)
+    }
+
+    pub fn solve_with_observer(mut self, observer: &mut dyn SolverObserver<T>) -> SolverResult<T> {
         let mut all_expansions: usize = 0;
-        let mut all_similars_removed: usize = 0;
+        let mut stats = SolverStats::default();
+        let solve_start = Instant::now();
+        let mut rng = StdRng::seed_from_u64(self.params.seed);
 
         loop {
             let iteration_start = Instant::now();
 
+            if let Some(time_limit) = self.params.time_limit
+                && iteration_start.duration_since(solve_start) >= time_limit
+            {
+                info!("Time limit of {:.3}s reached.", time_limit.as_secs_f64());
+                return self.create_result(all_expansions, stats);
+            }
+
+            if let Some(cancel) = &self.params.cancel
+                && cancel.load(Ordering::Relaxed)
+            {
+                info!("Cancelled.");
+                return self.create_result(all_expansions, stats);
+            }
+
             let expand_start = Instant::now();
             let nr_expanded = self.expand();
             let expand_duration = expand_start.elapsed();
+            stats.expand_time += expand_duration;
+            stats.peak_collection_size = stats.peak_collection_size.max(self.coll.len());
+
+            if nr_expanded > 0 {
+                self.archive_valid_solutions();
+                if self.params.prune_by_incumbent
+                    && let Some(incumbent) = self.archive.get_best()
+                {
+                    stats.pruned_by_incumbent +=
+                        self.coll.remove_worse_than(incumbent.data().fitness());
+                }
+            }
 
             let similar_start = Instant::now();
-            let similars_removed = if self.params.prune_similars {
-                self.coll
-                    .remove_similars(&self.is_similar, &self.similarity_hash)
+            let similars = if self.params.prune_similars {
+                self.coll.remove_similars_within(
+                    &self.is_similar,
+                    &self.similarity_hash,
+                    self.params.similar_time_budget,
+                )
             } else {
-                0
+                Vec::new()
             };
+            let similars_removed = similars.len();
             let similar_duration = similar_start.elapsed();
-            all_similars_removed += similars_removed;
+            stats.similar_removal_time += similar_duration;
+            stats.similars_removed += similars_removed;
 
             if nr_expanded == 0 {
-                return self.create_result(all_expansions, all_similars_removed);
+                if self.frontier.is_empty() {
+                    return self.create_result(all_expansions, stats);
+                }
+                // the taken nodes were dead ends, but the frontier still holds nodes of earlier iterations
+                self.coll = BeamsearchCollection::default();
+                self.take_from_frontier();
+                continue;
             }
 
             all_expansions += nr_expanded;
+            stats.iterations += 1;
+            let iteration = stats.iterations;
 
             let keep_best_start = Instant::now();
-            self.coll.keep_best(self.params.beam_width);
+            let selected_out = if self.params.diversity_beam_width > 0 {
+                self.coll.keep_best_and_diverse(
+                    self.params.beam_width,
+                    self.params.diversity_beam_width,
+                    &self.similarity_hash,
+                )
+            } else if let Some(temperature) = self.params.selection_temperature {
+                self.coll
+                    .keep_best_stochastic(self.params.beam_width, &mut rng, temperature)
+            } else {
+                0
+            };
+            let truncated = selected_out + self.push_to_frontier();
+            self.take_from_frontier();
             let keep_best_duration = keep_best_start.elapsed();
+            stats.keep_best_time += keep_best_duration;
+
+            observer.on_iteration(&IterationEvent {
+                iteration,
+                nr_expanded,
+                similars: &similars,
+                truncated,
+                kept: &self.coll,
+                incumbent: self.archive.get_best(),
+            });
+
+            if let Some(progress) = self.progress.as_mut() {
+                progress(&ProgressStats {
+                    iteration,
+                    collection_size: self.coll.len(),
+                    nr_expanded,
+                    similars_removed,
+                    best_fitness: self.coll.get_best().map(|best| best.data().fitness()),
+                });
+            }
 
             let iteration_duration = iteration_start.elapsed();
 
-            debug!(
+            trace!(
                 "Coll.-size: {}. Expanded {} (in {:.0}ms) and removed {} similars (in {:.0}ms), shrinked (in {:.0}ms), total time {:.0}ms",
                 self.coll.len(),
                 nr_expanded,
@@ -120,23 +637,59 @@ where
         }
     }
 
-    fn create_result(self, all_expansions: usize, all_similars_removed: usize) -> SolverResult<T> {
+    // Moves the surviving children into the frontier and returns the number of nodes the frontier dropped.
+    fn push_to_frontier(&mut self) -> usize {
+        let pushed = self.frontier.len() + self.coll.len();
+        for node in std::mem::take(&mut self.coll) {
+            self.frontier.push(node);
+        }
+        pushed - self.frontier.len()
+    }
+
+    // Takes the nodes to expand next from the frontier, best first.
+    fn take_from_frontier(&mut self) {
+        let batch_size = self.params.beam_width + self.params.diversity_beam_width;
+        while self.coll.len() < batch_size
+            && let Some(node) = self.frontier.pop_best()
+        {
+            self.coll.add(node);
+        }
+    }
+
+    fn create_result(mut self, all_expansions: usize, stats: SolverStats) -> SolverResult<T> {
         info!(
-            "Finished. Expanded {} and removed {} similars.",
-            all_expansions, all_similars_removed
+            "Finished after {} iterations. Expanded {} (in {:.0}ms) and removed {} similars (in {:.0}ms), shrinked (in {:.0}ms), peak coll.-size {}.",
+            stats.iterations,
+            all_expansions,
+            stats.expand_time.as_secs_f64() * 1000.0,
+            stats.similars_removed,
+            stats.similar_removal_time.as_secs_f64() * 1000.0,
+            stats.keep_best_time.as_secs_f64() * 1000.0,
+            stats.peak_collection_size
         );
 
-        let best = self.coll.get_best().cloned();
-        if best.is_some() && (&self.is_valid_solution)(&best.unwrap()) {
-            return SolverResult {
-                best: self.coll.get_best().cloned(),
-                nr_expansions: all_expansions,
-            };
+        while let Some(node) = self.frontier.pop_best() {
+            self.coll.add(node);
         }
+        self.coll.sort();
+        self.archive.sort();
+
         SolverResult {
-            best: None,
+            best: self.archive.get_best().cloned(),
             nr_expansions: all_expansions,
+            archive: self.archive.into_iter().collect(),
+            frontier: self.coll.into_iter().collect(),
+            stats,
+        }
+    }
+
+    fn archive_valid_solutions(&mut self) {
+        for node in self.coll.iter() {
+            if (self.is_valid_solution)(node) {
+                self.archive.add(node.clone());
+            }
         }
+        self.archive.keep_best(self.params.archive_size);
     }
 
     fn expand(&mut self) -> usize {
@@ -147,8 +700,7 @@ where
         let results: Vec<_> = old_coll
             .par_iter()
             .map(|node| {
-                let children = (self.expander)(node);
-                children
+                (self.expander)(node)
                     .into_iter()
                     .map(|child| node.new_child(child))
                     .collect::<Vec<_>>()
@@ -173,9 +725,18 @@ where
 #[cfg(test)]
 mod tests {
 
-    use crate::beamsearch::beamsearch_solver::{BeamsearchSolver, Node, Params, is_never_similar};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
+    use std::time::Duration;
+
+    use crate::beamsearch::beamsearch_solver::{
+        BeamsearchSolver, Node, Params, ParamsError, is_never_similar,
+    };
+
+    use super::super::frontier::PriorityFrontier;
     use super::super::mocks::TestNode;
+    use super::super::observer::TraceObserver;
 
     fn base_expander(n: &Node<TestNode>) -> Vec<TestNode> {
         if n.data().dummy_level < 2.0 {
@@ -184,7 +745,7 @@ mod tests {
                 dummy_level: n.data().dummy_level + 1.0,
             }];
         }
-        return vec![];
+        vec![]
     }
 
     fn bifurcate_expander<const LAST_LEVEL: i32>(n: &Node<TestNode>) -> Vec<TestNode> {
@@ -200,7 +761,45 @@ mod tests {
                 },
             ];
         }
-        return vec![];
+        vec![]
+    }
+
+    #[test]
+    fn test_params_builder_rejects_invalid_values() {
+        assert_eq!(
+            Params::builder().beam_width(0).build().err(),
+            Some(ParamsError::ZeroBeamWidth)
+        );
+        assert_eq!(
+            Params::builder().archive_size(0).build().err(),
+            Some(ParamsError::ZeroArchiveSize)
+        );
+        assert_eq!(
+            Params::builder().skip_penalty(-1.0).build().err(),
+            Some(ParamsError::Negative {
+                field: "skip_penalty",
+                value: -1.0
+            })
+        );
+        assert!(matches!(
+            Params::builder().selection_temperature(f64::NAN).build(),
+            Err(ParamsError::Negative {
+                field: "selection_temperature",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_params_builder_starts_from_defaults() {
+        let params = Params::builder()
+            .time_limit(Duration::from_secs(3))
+            .build()
+            .unwrap();
+
+        assert_eq!(params.beam_width, 100);
+        assert!(params.prune_similars);
+        assert_eq!(params.time_limit, Some(Duration::from_secs(3)));
     }
 
     #[test]
@@ -210,18 +809,19 @@ mod tests {
             base_expander,
             is_never_similar,
             |_| 0,
-            |_n| true,
-            Params {
-                beam_width: 2,
-                prune_similars: true,
-            },
+            |n| n.data().dummy_level == 2.0,
+            Params::builder()
+                .beam_width(2)
+                .prune_similars(true)
+                .build()
+                .unwrap(),
         )
         .solve()
         .best
         .unwrap();
 
-        assert!(&result.data().dummy_fitness == &2.0);
-        assert!(&result.data().dummy_level == &2.0);
+        assert!(result.data().dummy_fitness == 2.0);
+        assert!(result.data().dummy_level == 2.0);
     }
 
     #[test]
@@ -231,19 +831,53 @@ mod tests {
             bifurcate_expander::<10>,
             is_never_similar,
             |_| 0,
-            |_n| true,
-            Params {
-                beam_width: 4,
-                prune_similars: true,
-            },
+            |n| n.data().dummy_level == 10.0,
+            Params::builder().beam_width(4).build().unwrap(),
         )
         .solve();
 
         let best = result.best.unwrap();
 
         assert_eq!(result.nr_expansions, 2 + 4 + 8 * 8);
-        assert!(&best.data().dummy_fitness == &10.0);
-        assert!(&best.data().dummy_level == &10.0);
+        assert!(best.data().dummy_fitness == 10.0);
+        assert!(best.data().dummy_level == 10.0);
+    }
+
+    #[test]
+    fn test_streaming_expander_solves_like_vec_expander() {
+        let streaming = |n: &Node<TestNode>| {
+            let (fitness, level) = (n.data().dummy_fitness, n.data().dummy_level);
+            (0..2).filter(move |_| level < 10.0).map(move |_| TestNode {
+                dummy_fitness: fitness + 1.0,
+                dummy_level: level + 1.0,
+            })
+        };
+        let params = Params {
+            beam_width: 4,
+            ..Default::default()
+        };
+
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            streaming,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 10.0,
+            params.clone(),
+        )
+        .solve();
+        let expected = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<10>,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 10.0,
+            params,
+        )
+        .solve();
+
+        assert_eq!(result.nr_expansions, expected.nr_expansions);
+        assert!(result.best.unwrap().data() == expected.best.unwrap().data());
     }
 
     #[test]
@@ -257,11 +891,33 @@ mod tests {
             Params {
                 beam_width: 1000,
                 prune_similars: true,
+                ..Default::default()
             },
         )
         .solve();
 
         assert_eq!(result.nr_expansions, 10 * 2);
+        assert_eq!(result.stats.similars_removed, 10);
+    }
+
+    #[test]
+    fn test_stats_count_iterations_and_peak_collection_size() {
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<10>,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 10.0,
+            Params {
+                beam_width: 4,
+                ..Default::default()
+            },
+        )
+        .solve();
+
+        assert_eq!(result.stats.iterations, 10);
+        assert_eq!(result.stats.peak_collection_size, 8);
+        assert_eq!(result.stats.similars_removed, 0);
     }
 
     #[test]
@@ -270,11 +926,12 @@ mod tests {
             vec![TestNode::default()],
             bifurcate_expander::<10>,
             |x, y| x.data() == y.data(),
-            |n| n.data().dummy_level as u32,
+            |n| n.data().dummy_level as u64,
             |_n| true,
             Params {
                 beam_width: 1000,
                 prune_similars: true,
+                ..Default::default()
             },
         )
         .solve();
@@ -293,9 +950,372 @@ mod tests {
             Params {
                 beam_width: 2,
                 prune_similars: true,
+                ..Default::default()
             },
         )
         .solve();
         assert!(result.best.is_none());
     }
+
+    #[test]
+    fn test_trace_observer_writes_one_section_per_iteration() {
+        let mut trace = TraceObserver::new(Vec::new());
+
+        BeamsearchSolver::new(
+            vec![TestNode::default()],
+            base_expander,
+            is_never_similar,
+            |_| 0,
+            |_n| true,
+            Params {
+                beam_width: 2,
+                prune_similars: true,
+                ..Default::default()
+            },
+        )
+        .solve_with_observer(&mut trace);
+
+        let trace = String::from_utf8(trace.into_inner()).unwrap();
+
+        assert_eq!(trace.matches("== Iteration").count(), 2);
+        assert!(trace.contains("== Iteration 1 =="));
+        assert!(trace.contains("== Iteration 2 =="));
+        assert!(trace.contains("best fitness: 2"));
+    }
+
+    #[test]
+    fn test_trace_observer_lists_nodes_pruned_as_similar() {
+        let mut trace = TraceObserver::new(Vec::new());
+
+        BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<1>,
+            |x, y| x.data() == y.data(),
+            |_| 0,
+            |_n| true,
+            Params {
+                beam_width: 2,
+                prune_similars: true,
+                ..Default::default()
+            },
+        )
+        .solve_with_observer(&mut trace);
+
+        let trace = String::from_utf8(trace.into_inner()).unwrap();
+
+        assert_eq!(
+            trace,
+            "== Iteration 1 ==\n\
+             expanded: 2\n\
+             pruned by beam width: 0\n\
+             best fitness: 1\n\
+             pruned 1 nodes as similar:\n  fitness 1.00 level 1.00\n\
+             kept 1 nodes:\n  fitness 1.00 level 1.00\n"
+        );
+    }
+
+    fn early_complete_expander(n: &Node<TestNode>) -> Vec<TestNode> {
+        // level 100 marks complete solutions, the early one (fitness 5) is worse than its partial sibling
+        let child = |dummy_fitness, dummy_level| TestNode {
+            dummy_fitness,
+            dummy_level,
+        };
+        match n.data().dummy_level as i32 {
+            0 => vec![child(5.0, 100.0), child(1.0, 1.0)],
+            1 => vec![child(2.0, 2.0)],
+            2 => vec![child(10.0, 100.0)],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_incumbent_prunes_worse_partial_nodes() {
+        // the root completes at fitness 5 right away, its other branch grows by 1 per level up to level 10
+        let expander = |n: &Node<TestNode>| {
+            let child = |dummy_fitness, dummy_level| TestNode {
+                dummy_fitness,
+                dummy_level,
+            };
+            let level = n.data().dummy_level;
+            if level == 0.0 {
+                vec![child(5.0, 100.0), child(1.0, 1.0), child(1.0, 1.0)]
+            } else if level < 10.0 {
+                vec![
+                    child(level + 1.0, level + 1.0),
+                    child(level + 1.0, level + 1.0),
+                ]
+            } else {
+                vec![]
+            }
+        };
+        let solve = |prune_by_incumbent| {
+            BeamsearchSolver::new(
+                vec![TestNode::default()],
+                expander,
+                is_never_similar,
+                |_| 0,
+                |n| n.data().dummy_level == 100.0,
+                Params {
+                    beam_width: 1000,
+                    prune_by_incumbent,
+                    ..Default::default()
+                },
+            )
+            .solve()
+        };
+
+        let unpruned = solve(false);
+        let pruned = solve(true);
+
+        assert_eq!(pruned.best.unwrap().data().dummy_fitness, 5.0);
+        assert_eq!(unpruned.best.unwrap().data().dummy_fitness, 5.0);
+        // levels 1 to 5 are still expanded, their children at level 6 are already worse than 5
+        assert_eq!(pruned.nr_expansions, 3 + 4 + 8 + 16 + 32 + 64);
+        assert_eq!(pruned.stats.pruned_by_incumbent, 64);
+        assert!(pruned.nr_expansions < unpruned.nr_expansions);
+    }
+
+    #[test]
+    fn test_priority_frontier_backtracks_from_dead_end() {
+        // the better child of the root is a dead end, only its sibling leads to a solution at level 2
+        let expander = |n: &Node<TestNode>| {
+            let child = |dummy_fitness, dummy_level| TestNode {
+                dummy_fitness,
+                dummy_level,
+            };
+            match (n.data().dummy_level as i32, n.data().dummy_fitness as i32) {
+                (0, _) => vec![child(1.0, 1.0), child(2.0, 1.0)],
+                (1, 2) => vec![child(3.0, 2.0)],
+                _ => vec![],
+            }
+        };
+        let solver = || {
+            BeamsearchSolver::new(
+                vec![TestNode::default()],
+                expander,
+                is_never_similar,
+                |_| 0,
+                |n| n.data().dummy_level == 2.0,
+                Params {
+                    beam_width: 1,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let beam = solver().solve();
+        let best_first = solver().with_frontier(PriorityFrontier::default()).solve();
+
+        assert!(beam.best.is_none());
+        assert_eq!(best_first.best.unwrap().data().dummy_fitness, 3.0);
+        assert_eq!(best_first.nr_expansions, 3);
+    }
+
+    #[test]
+    fn test_archive_keeps_solution_truncated_from_beam() {
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            early_complete_expander,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 100.0,
+            Params {
+                beam_width: 1,
+                archive_size: 2,
+                ..Default::default()
+            },
+        )
+        .solve();
+
+        assert_eq!(result.best.unwrap().data().dummy_fitness, 5.0);
+        let archived: Vec<f64> = result
+            .archive
+            .iter()
+            .map(|n| n.data().dummy_fitness)
+            .collect();
+        assert_eq!(archived, vec![5.0, 10.0]);
+    }
+
+    #[test]
+    fn test_best_is_earlier_solution_if_final_beam_is_a_dead_end() {
+        let dead_end_expander = |n: &Node<TestNode>| {
+            let child = |dummy_fitness, dummy_level| TestNode {
+                dummy_fitness,
+                dummy_level,
+            };
+            match n.data().dummy_level as i32 {
+                0 => vec![child(5.0, 100.0), child(1.0, 1.0)],
+                1 => vec![child(2.0, 2.0)],
+                _ => vec![],
+            }
+        };
+
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            dead_end_expander,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 100.0,
+            Params {
+                beam_width: 1,
+                ..Default::default()
+            },
+        )
+        .solve();
+
+        assert_eq!(result.frontier[0].data().dummy_level, 2.0);
+        assert_eq!(result.best.unwrap().data().dummy_fitness, 5.0);
+    }
+
+    #[test]
+    fn test_time_limit_stops_endless_search() {
+        // far more levels than can be expanded within the time limit
+        const LAST_LEVEL: f64 = 1e7;
+        let endless_expander = |n: &Node<TestNode>| {
+            if n.data().dummy_level >= LAST_LEVEL {
+                return vec![];
+            }
+            vec![TestNode {
+                dummy_fitness: n.data().dummy_fitness + 1.0,
+                dummy_level: n.data().dummy_level + 1.0,
+            }]
+        };
+
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            endless_expander,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 2.0,
+            Params {
+                beam_width: 2,
+                time_limit: Some(std::time::Duration::from_millis(50)),
+                ..Default::default()
+            },
+        )
+        .solve();
+
+        assert!(result.nr_expansions >= 2);
+        assert!(result.nr_expansions < LAST_LEVEL as usize);
+        assert_eq!(result.best.unwrap().data().dummy_level, 2.0);
+    }
+
+    #[test]
+    fn test_progress_callback_is_called_per_iteration() {
+        let stats = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let recorded = stats.clone();
+
+        BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<3>,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 3.0,
+            Params {
+                beam_width: 2,
+                ..Default::default()
+            },
+        )
+        .with_progress(move |s| recorded.borrow_mut().push(*s))
+        .solve();
+
+        let stats = stats.borrow();
+        let iterations: Vec<usize> = stats.iter().map(|s| s.iteration).collect();
+        let expanded: Vec<usize> = stats.iter().map(|s| s.nr_expanded).collect();
+        assert_eq!(iterations, vec![1, 2, 3]);
+        assert_eq!(expanded, vec![2, 4, 4]);
+        assert!(stats.iter().all(|s| s.collection_size == 2));
+        assert_eq!(stats[2].best_fitness, Some(3.0));
+    }
+
+    #[test]
+    fn test_cancel_flag_stops_search_after_current_iteration() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_after_second = cancel.clone();
+
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<10>,
+            is_never_similar,
+            |_| 0,
+            |n| n.data().dummy_level >= 2.0,
+            Params {
+                beam_width: 2,
+                cancel: Some(cancel),
+                ..Default::default()
+            },
+        )
+        .with_progress(move |s| {
+            if s.iteration == 2 {
+                cancel_after_second.store(true, Ordering::Relaxed);
+            }
+        })
+        .solve();
+
+        assert_eq!(result.stats.iterations, 2);
+        assert_eq!(result.best.unwrap().data().dummy_level, 2.0);
+    }
+
+    #[test]
+    fn test_similar_time_budget_skips_slow_similarity_checks() {
+        let slow_is_similar = |x: &Node<TestNode>, y: &Node<TestNode>| {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            x.data() == y.data()
+        };
+        let start = std::time::Instant::now();
+
+        let result = BeamsearchSolver::new(
+            vec![TestNode::default()],
+            bifurcate_expander::<5>,
+            slow_is_similar,
+            |_| 0,
+            |n| n.data().dummy_level == 5.0,
+            Params {
+                beam_width: 1000,
+                similar_time_budget: Some(std::time::Duration::ZERO),
+                ..Default::default()
+            },
+        )
+        .solve();
+
+        // without the budget, the single similarity group of 32 nodes alone needs seconds of comparisons
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(result.best.unwrap().data().dummy_level, 5.0);
+        assert_eq!(result.frontier.len(), 32);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_params_serde_round_trip() {
+        let params = Params::builder()
+            .beam_width(7)
+            .objective(super::Objective::MinMakespan)
+            .lateness_penalty(2.5)
+            .time_limit(Duration::from_millis(1500))
+            .checkpoint(
+                "checkpoint.txt".into(),
+                super::CheckpointTrigger::Interval(Duration::from_secs(3)),
+            )
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_string(&params).unwrap();
+        let restored: Params = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.beam_width, 7);
+        assert_eq!(restored.objective, super::Objective::MinMakespan);
+        assert_eq!(restored.lateness_penalty, Some(2.5));
+        assert_eq!(restored.time_limit, Some(Duration::from_millis(1500)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_params_deserialize_missing_fields_as_defaults() {
+        let params: Params = serde_json::from_str(r#"{"beam_width": 5}"#).unwrap();
+
+        assert_eq!(params.beam_width, 5);
+        assert_eq!(params.archive_size, Params::default().archive_size);
+        assert!(params.prune_similars);
+    }
 }
@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use super::beamsearch_collection::BeamsearchNode;
+use super::beamsearch_solver::Node;
+
+/// The set of open nodes of a search. Lower fitness is better, as everywhere in the solver.
+pub trait Frontier<T>
+where
+    T: BeamsearchNode,
+{
+    fn push(&mut self, node: Node<T>);
+    fn pop_best(&mut self) -> Option<Node<T>>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keeps only the `width` best nodes pushed so far, the worst ones are dropped on overflow. This is the frontier
+/// of the beam search.
+pub struct BeamFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    width: usize,
+    // sorted from best to worst up to `sorted_len`, the nodes behind were pushed since
+    nodes: VecDeque<Node<T>>,
+    sorted_len: usize,
+}
+
+impl<T> BeamFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            nodes: VecDeque::new(),
+            sorted_len: 0,
+        }
+    }
+
+    // Sorts the nodes and truncates them to the width. Among nodes of equal fitness, the ones pushed first are
+    // kept and popped first.
+    fn sort_and_truncate(&mut self) {
+        if self.sorted_len == self.nodes.len() {
+            return;
+        }
+        self.nodes
+            .make_contiguous()
+            .sort_by(|a, b| a.data().fitness().total_cmp(&b.data().fitness()));
+        self.nodes.truncate(self.width);
+        self.sorted_len = self.nodes.len();
+    }
+}
+
+impl<T> Frontier<T> for BeamFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    fn push(&mut self, node: Node<T>) {
+        self.nodes.push_back(node);
+        // truncating only once the width is exceeded twice keeps pushes amortized O(log width)
+        if self.nodes.len() > self.width.saturating_mul(2) {
+            self.sort_and_truncate();
+        }
+    }
+
+    fn pop_best(&mut self) -> Option<Node<T>> {
+        self.sort_and_truncate();
+        self.sorted_len = self.sorted_len.saturating_sub(1);
+        self.nodes.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len().min(self.width)
+    }
+}
+
+// Reverses the order on fitness, such that the max-heap pops the node with the lowest fitness first.
+struct ByFitness<T: BeamsearchNode>(Node<T>);
+
+impl<T: BeamsearchNode> PartialEq for ByFitness<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: BeamsearchNode> Eq for ByFitness<T> {}
+
+impl<T: BeamsearchNode> PartialOrd for ByFitness<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: BeamsearchNode> Ord for ByFitness<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.data().fitness().total_cmp(&self.0.data().fitness())
+    }
+}
+
+/// Unbounded frontier for best-first search.
+pub struct PriorityFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    heap: BinaryHeap<ByFitness<T>>,
+}
+
+impl<T> Default for PriorityFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    fn default() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+        }
+    }
+}
+
+impl<T> Frontier<T> for PriorityFrontier<T>
+where
+    T: BeamsearchNode,
+{
+    fn push(&mut self, node: Node<T>) {
+        self.heap.push(ByFitness(node));
+    }
+
+    fn pop_best(&mut self) -> Option<Node<T>> {
+        self.heap.pop().map(|ByFitness(node)| node)
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mocks::TestNode;
+    use super::*;
+
+    fn push_all(frontier: &mut impl Frontier<TestNode>, fitnesses: &[f64]) {
+        for &dummy_fitness in fitnesses {
+            frontier.push(Node::new_root(TestNode {
+                dummy_fitness,
+                ..Default::default()
+            }));
+        }
+    }
+
+    fn pop_all(frontier: &mut impl Frontier<TestNode>) -> Vec<f64> {
+        std::iter::from_fn(|| frontier.pop_best())
+            .map(|node| node.data().dummy_fitness)
+            .collect()
+    }
+
+    #[test]
+    fn test_priority_frontier_pops_in_best_first_order() {
+        let mut frontier = PriorityFrontier::default();
+        push_all(&mut frontier, &[3.0, 1.0, 4.0, 1.5, 2.0]);
+
+        assert_eq!(frontier.len(), 5);
+        assert_eq!(pop_all(&mut frontier), vec![1.0, 1.5, 2.0, 3.0, 4.0]);
+        assert!(frontier.is_empty());
+    }
+
+    #[test]
+    fn test_beam_frontier_keeps_only_best_width_nodes() {
+        let mut frontier = BeamFrontier::new(3);
+        push_all(&mut frontier, &[3.0, 1.0, 4.0, 1.5, 2.0]);
+
+        assert_eq!(frontier.len(), 3);
+        assert_eq!(pop_all(&mut frontier), vec![1.0, 1.5, 2.0]);
+    }
+
+    #[test]
+    fn test_frontiers_interleave_push_and_pop() {
+        let mut beam = BeamFrontier::new(10);
+        let mut priority = PriorityFrontier::default();
+
+        for frontier in [
+            &mut beam as &mut dyn Frontier<TestNode>,
+            &mut priority as &mut dyn Frontier<TestNode>,
+        ] {
+            for &dummy_fitness in &[5.0, 2.0] {
+                frontier.push(Node::new_root(TestNode {
+                    dummy_fitness,
+                    ..Default::default()
+                }));
+            }
+            assert_eq!(frontier.pop_best().unwrap().data().dummy_fitness, 2.0);
+            frontier.push(Node::new_root(TestNode {
+                dummy_fitness: 1.0,
+                ..Default::default()
+            }));
+            assert_eq!(frontier.pop_best().unwrap().data().dummy_fitness, 1.0);
+            assert_eq!(frontier.pop_best().unwrap().data().dummy_fitness, 5.0);
+            assert!(frontier.pop_best().is_none());
+        }
+    }
+}
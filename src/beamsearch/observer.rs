@@ -0,0 +1,111 @@
+use std::io::Write;
+
+use super::beamsearch_collection::{BeamsearchCollection, BeamsearchNode};
+use super::beamsearch_solver::Node;
+
+pub struct IterationEvent<'a, T>
+where
+    T: BeamsearchNode + Send + Sync,
+{
+    pub iteration: usize,
+    pub nr_expanded: usize,
+    /// The nodes removed for being similar to a better node.
+    pub similars: &'a [Node<T>],
+    pub truncated: usize,
+    pub kept: &'a BeamsearchCollection<T>,
+    /// The best valid solution found so far, if any.
+    pub incumbent: Option<&'a Node<T>>,
+}
+
+/// Summary of an iteration handed to the progress callback of the solver.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgressStats {
+    pub iteration: usize,
+    pub collection_size: usize,
+    pub nr_expanded: usize,
+    pub similars_removed: usize,
+    /// Fitness of the best node kept in the beam, which need not be a valid solution.
+    pub best_fitness: Option<f64>,
+}
+
+/// Gets notified by the solver after every iteration that expanded at least one node.
+pub trait SolverObserver<T>
+where
+    T: BeamsearchNode + Send + Sync,
+{
+    fn on_iteration(&mut self, event: &IterationEvent<T>);
+}
+
+pub struct NoObserver;
+
+impl<T> SolverObserver<T> for NoObserver
+where
+    T: BeamsearchNode + Send + Sync,
+{
+    fn on_iteration(&mut self, _event: &IterationEvent<T>) {}
+}
+
+/// Writes a human-readable log of the decisions taken in each iteration.
+pub struct TraceObserver<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TraceObserver<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_iteration<T>(&mut self, event: &IterationEvent<T>) -> std::io::Result<()>
+    where
+        T: BeamsearchNode + Send + Sync,
+    {
+        writeln!(self.out, "== Iteration {} ==", event.iteration)?;
+        writeln!(self.out, "expanded: {}", event.nr_expanded)?;
+        writeln!(self.out, "pruned by beam width: {}", event.truncated)?;
+        match event.kept.get_best() {
+            Some(best) => writeln!(self.out, "best fitness: {}", best.data().fitness())?,
+            None => writeln!(self.out, "best fitness: -")?,
+        }
+        writeln!(
+            self.out,
+            "pruned {} nodes as similar:",
+            event.similars.len()
+        )?;
+        self.write_nodes(event.similars)?;
+        writeln!(self.out, "kept {} nodes:", event.kept.len())?;
+        self.write_nodes(event.kept)
+    }
+
+    fn write_nodes<'a, T>(
+        &mut self,
+        nodes: impl IntoIterator<Item = &'a Node<T>>,
+    ) -> std::io::Result<()>
+    where
+        T: BeamsearchNode + 'a,
+    {
+        for node in nodes {
+            writeln!(
+                self.out,
+                "  fitness {:.2} level {:.2}",
+                node.data().fitness(),
+                node.data().level()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, W> SolverObserver<T> for TraceObserver<W>
+where
+    T: BeamsearchNode + Send + Sync,
+    W: Write,
+{
+    fn on_iteration(&mut self, event: &IterationEvent<T>) {
+        self.write_iteration(event)
+            .expect("Failed to write solver trace");
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 struct InnerParentTreeNode<T> {
     parent: Option<ParentTreeNode<T>>,
+    depth: usize,
     data: T,
 }
 
@@ -12,7 +13,11 @@ pub struct ParentTreeNode<T> {
 impl<T> ParentTreeNode<T> {
     pub fn new_root(data: T) -> Self {
         Self {
-            inner: Arc::new(InnerParentTreeNode { parent: None, data }),
+            inner: Arc::new(InnerParentTreeNode {
+                parent: None,
+                depth: 0,
+                data,
+            }),
         }
     }
 
@@ -21,6 +26,7 @@ impl<T> ParentTreeNode<T> {
             parent: Some(ParentTreeNode {
                 inner: Arc::clone(&self.inner),
             }),
+            depth: self.inner.depth + 1,
             data,
         });
 
@@ -31,6 +37,11 @@ impl<T> ParentTreeNode<T> {
         &self.inner.data
     }
 
+    /// Number of parents, 0 for the root.
+    pub fn depth(&self) -> usize {
+        self.inner.depth
+    }
+
     pub fn is_root(&self) -> bool {
         self.inner.parent.is_none()
     }
@@ -46,6 +57,14 @@ impl<T> ParentTreeNode<T> {
     pub fn ancestors(&self) -> impl Iterator<Item = &ParentTreeNode<T>> {
         std::iter::successors(Some(self), |node| node.parent())
     }
+
+    /// The data of all ancestors, starting with the root and ending with this node.
+    pub fn path_from_root(&self) -> Vec<&T> {
+        let mut path = Vec::with_capacity(self.depth() + 1);
+        path.extend(self.ancestors().map(|node| node.data()));
+        path.reverse();
+        path
+    }
 }
 
 impl<T> Clone for ParentTreeNode<T> {
@@ -56,10 +75,22 @@ impl<T> Clone for ParentTreeNode<T> {
     }
 }
 
+// Dropping the last handle of a node would otherwise drop its parent recursively, which overflows the stack
+// for long chains. Instead, the parents that are not shared are unlinked and dropped one after another.
+impl<T> Drop for ParentTreeNode<T> {
+    fn drop(&mut self) {
+        let mut parent = Arc::get_mut(&mut self.inner).and_then(|inner| inner.parent.take());
+        while let Some(mut node) = parent {
+            parent = Arc::get_mut(&mut node.inner).and_then(|inner| inner.parent.take());
+        }
+    }
+}
+
 //unsafe impl<T> Send for ParentTreeNode<T> where T: Send {}
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
     use std::rc::Rc;
 
     use super::*;
@@ -99,6 +130,8 @@ mod tests {
 
         assert_eq!(ancestors.len(), 2);
         assert_eq!(ancestors, vec![2.0, 1.0]);
+        assert_eq!(root.depth(), 0);
+        assert_eq!(child.depth(), 1);
     }
 
     #[test]
@@ -115,6 +148,9 @@ mod tests {
 
         assert_eq!(ancestors.len(), 10);
         assert_eq!(ancestors, (1..=10).rev().collect::<Vec<i32>>());
+        assert_eq!(child.depth(), 9);
+        let path: Vec<i32> = child.path_from_root().into_iter().copied().collect();
+        assert_eq!(path, (1..=10).collect::<Vec<i32>>());
     }
 
     #[test]
@@ -140,6 +176,148 @@ mod tests {
         assert_eq!(Rc::strong_count(&child_counter), 1);
     }
 
+    // Beam-like workload: every level, each of `width` survivors gets `branching` children, of which `width`
+    // survive. The data mimics a search node of a few words. `after_level` sees the survivors of every level.
+    fn run_deep_search<N>(
+        (width, branching, depth): (usize, usize, usize),
+        root: N,
+        mut new_child: impl FnMut(&N, [u64; 6]) -> N,
+        mut after_level: impl FnMut(&mut Vec<N>),
+    ) -> Vec<N> {
+        let mut beam = vec![root];
+        for level in 0..depth {
+            let mut children = Vec::with_capacity(beam.len() * branching);
+            for (i, node) in beam.iter().enumerate() {
+                for c in 0..branching {
+                    children.push(new_child(node, [level as u64, i as u64, c as u64, 0, 0, 0]));
+                }
+            }
+            // deterministic spread over the children, so that many parents lose all their children
+            let step = (children.len() / width).max(1);
+            beam = children.into_iter().step_by(step).take(width).collect();
+            after_level(&mut beam);
+        }
+        beam
+    }
+
+    struct ArenaNode {
+        parent: Option<u32>,
+        data: [u64; 6],
+    }
+
+    // Keeps only the survivors and their ancestors, moving them to the front in their old order, so that
+    // parents still come before their children.
+    fn compact(arena: &mut Vec<ArenaNode>, beam: &mut [u32]) {
+        let mut live = vec![false; arena.len()];
+        for &node in beam.iter() {
+            let mut current = Some(node);
+            while let Some(index) = current
+                && !live[index as usize]
+            {
+                live[index as usize] = true;
+                current = arena[index as usize].parent;
+            }
+        }
+        let mut new_index = vec![u32::MAX; arena.len()];
+        let mut len = 0;
+        for old in 0..arena.len() {
+            if live[old] {
+                new_index[old] = len as u32;
+                let parent = arena[old].parent.map(|parent| new_index[parent as usize]);
+                arena.swap(len, old);
+                arena[len].parent = parent;
+                len += 1;
+            }
+        }
+        arena.truncate(len);
+        for node in beam.iter_mut() {
+            *node = new_index[*node as usize];
+        }
+    }
+
+    // Compares the Arc nodes with a slab of nodes linked by parent index, which is either never freed or
+    // compacted after every level by keeping the survivors and their ancestors. In the solver, the slab
+    // could be filled in the sequential loop that adds the expanded children to the collection and compacted
+    // after the collection is shrunk, with the archive and the frontier as further roots. Nodes would then
+    // need the slab to reach their parents, which changes every use of `parent` and `ancestors`.
+    // run with `cargo test --release bench_deep_search -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_deep_search_arc_nodes_vs_index_arena() {
+        let shape = (20_000, 10, 46);
+
+        let start = std::time::Instant::now();
+        let beam = run_deep_search(
+            shape,
+            ParentTreeNode::new_root([0u64; 6]),
+            |node, data| node.new_child(data),
+            |_| {},
+        );
+        let path_len = beam[0].ancestors().count();
+        drop(beam);
+        println!(
+            "arc nodes:          {:?} (path length {path_len})",
+            start.elapsed()
+        );
+
+        for compacting in [false, true] {
+            let start = std::time::Instant::now();
+            let arena = RefCell::new(vec![ArenaNode {
+                parent: None,
+                data: [0; 6],
+            }]);
+            let mut peak_len = 0;
+            let beam = run_deep_search(
+                shape,
+                0u32,
+                |&parent, data| {
+                    let mut arena = arena.borrow_mut();
+                    arena.push(ArenaNode {
+                        parent: Some(parent),
+                        data,
+                    });
+                    (arena.len() - 1) as u32
+                },
+                |beam| {
+                    let mut arena = arena.borrow_mut();
+                    peak_len = peak_len.max(arena.len());
+                    if compacting {
+                        compact(&mut arena, beam);
+                    }
+                },
+            );
+            let arena = arena.into_inner();
+            let path_len =
+                std::iter::successors(Some(beam[0]), |&i| arena[i as usize].parent).count();
+            let checksum: u64 = arena.iter().map(|node| node.data[0]).sum();
+            println!(
+                "{:<19} {:?} (path length {path_len}, peak {peak_len} slots, checksum {checksum})",
+                if compacting {
+                    "compacting arena:"
+                } else {
+                    "append-only arena:"
+                },
+                start.elapsed(),
+            );
+        }
+    }
+
+    #[test]
+    fn dropping_long_chain_does_not_overflow_stack() {
+        let root_counter = Rc::new(0);
+        let mut node = ParentTreeNode::new_root(root_counter.clone());
+        for _ in 0..100_000 {
+            node = node.new_child(Rc::new(0));
+        }
+        let shared_parent = node.parent().unwrap().clone();
+
+        drop(node);
+
+        assert_eq!(Rc::strong_count(&root_counter), 2);
+        drop(shared_parent);
+        assert_eq!(Rc::strong_count(&root_counter), 1);
+    }
+
     #[test]
     fn is_root() {
         let root = ParentTreeNode::new_root(1.0);
@@ -147,5 +325,6 @@ mod tests {
 
         assert!(root.is_root());
         assert!(!child.is_root());
+        assert_eq!(root.path_from_root(), vec![&1.0]);
     }
 }
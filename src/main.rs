@@ -6,13 +6,19 @@ mod tsp;
 
 use env_logger::Builder;
 use log::{info, warn};
+use rayon::prelude::*;
 use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::Write;
 use std::sync::Arc;
-use std::{fs::read, path::PathBuf};
-use tsp::{TSPInstance, TSPSolution, solve_tsp};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{
+    fs::read,
+    path::{Path, PathBuf},
+};
+use tsp::{DistanceRounding, TSPInstance, TSPSolution, solve_tsp};
 
 fn init_logger() {
     let log_file = OpenOptions::new()
@@ -65,7 +71,13 @@ pub struct BestKnown {
     pub name: String,
 }
 
-pub fn read_all_instances(best_known_file: PathBuf) -> Vec<BestKnown> {
+/// Reads every instance listed in the best known file, with the rounding convention its best knowns were
+/// computed with.
+pub fn read_all_instances(
+    instances_dir: &Path,
+    best_known_file: PathBuf,
+    rounding: DistanceRounding,
+) -> Vec<BestKnown> {
     assert!(best_known_file.is_file());
 
     let mut best_knowns = Vec::<BestKnown>::new();
@@ -78,16 +90,16 @@ pub fn read_all_instances(best_known_file: PathBuf) -> Vec<BestKnown> {
         let instance_file = cols[0];
         let best_dist: f64 = cols[1].parse().unwrap();
         let best_path: Vec<usize> = cols[2..]
-            .to_vec()
-            .into_iter()
+            .iter()
             .map(|n| n.parse().unwrap())
             .chain(std::iter::once(0))
             .collect();
 
-        let instance = Arc::new(TSPInstance::from_file(PathBuf::from(format!(
-            "instances/SolomonPotvinBengio/{}",
-            &instance_file
-        ))));
+        let instance = Arc::new(
+            TSPInstance::from_file(instances_dir.join(instance_file))
+                .unwrap_or_else(|e| panic!("Failed to parse instance {instance_file}: {e}"))
+                .with_distance_rounding(rounding),
+        );
 
         let solution = TSPSolution::new(instance.clone(), best_path);
 
@@ -108,6 +120,160 @@ pub fn read_all_instances(best_known_file: PathBuf) -> Vec<BestKnown> {
     best_knowns
 }
 
+const USAGE: &str = "Usage: rsopt [--config FILE] [--beam-width N] [--prune-similars true|false] \
+[--time-limit SECONDS] [--instances-dir DIR] [--best-known FILE] [--output FILE] [--filter PATTERN] \
+[--parallel-instances N] [--json-output FILE] [--csv-output FILE] \
+[--distance-rounding none|truncate-one-decimal|round-two-decimals]";
+
+#[derive(Debug, PartialEq)]
+struct RunOptions {
+    beam_width: usize,
+    prune_similars: bool,
+    time_limit: Option<Duration>,
+    instances_dir: PathBuf,
+    /// Defaults to best_known.txt in the instances directory.
+    best_known: Option<PathBuf>,
+    output: PathBuf,
+    /// Only instances whose name matches are solved, see `matches_filter`.
+    filter: Option<String>,
+    /// Number of instances solved at the same time, 1 solves them one after another. The solves share the
+    /// global thread pool, so this limits how many instances are in flight, not how many threads they use.
+    parallel_instances: usize,
+    /// If set, the per-instance results and their summary are also written there as JSON.
+    json_output: Option<PathBuf>,
+    /// If set, the per-instance results are also written there as CSV.
+    csv_output: Option<PathBuf>,
+    /// Applied to the distances of every instance, to match the convention of the best knowns.
+    distance_rounding: DistanceRounding,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            beam_width: 1e7 as usize,
+            prune_similars: true,
+            time_limit: None,
+            instances_dir: PathBuf::from("instances/SolomonPotvinBengio"),
+            best_known: None,
+            output: PathBuf::from("last_result.txt"),
+            filter: None,
+            parallel_instances: 1,
+            json_output: None,
+            csv_output: None,
+            distance_rounding: DistanceRounding::None,
+        }
+    }
+}
+
+impl RunOptions {
+    fn best_known_file(&self) -> PathBuf {
+        self.best_known
+            .clone()
+            .unwrap_or_else(|| self.instances_dir.join("best_known.txt"))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value {value} for {key}");
+        match key {
+            "beam_width" => self.beam_width = value.parse().map_err(|_| invalid())?,
+            "prune_similars" => self.prune_similars = value.parse().map_err(|_| invalid())?,
+            "time_limit" => {
+                let seconds: f64 = value.parse().map_err(|_| invalid())?;
+                self.time_limit =
+                    Some(Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?);
+            }
+            "instances_dir" => self.instances_dir = PathBuf::from(value),
+            "best_known" => self.best_known = Some(PathBuf::from(value)),
+            "output" => self.output = PathBuf::from(value),
+            "filter" => self.filter = Some(value.to_string()),
+            "parallel_instances" => {
+                self.parallel_instances = value.parse().map_err(|_| invalid())?
+            }
+            "json_output" => self.json_output = Some(PathBuf::from(value)),
+            "csv_output" => self.csv_output = Some(PathBuf::from(value)),
+            "distance_rounding" => {
+                self.distance_rounding = match value {
+                    "none" => DistanceRounding::None,
+                    "truncate-one-decimal" => DistanceRounding::TruncateOneDecimal,
+                    "round-two-decimals" => DistanceRounding::RoundTwoDecimals,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(format!("Unknown option {key}")),
+        }
+        Ok(())
+    }
+
+    /// Applies a config file of `key = value` lines, a flat subset of TOML. Keys are the command line options
+    /// with underscores, e.g. `beam_width = 1000`; strings may be quoted. Blank lines and # comments are
+    /// ignored, missing keys keep their current value.
+    fn apply_config(&mut self, content: &str) -> Result<(), String> {
+        for (line_nr, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("Line {}: expected key = value", line_nr + 1))?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            self.set(key.trim(), value)
+                .map_err(|e| format!("Line {}: {e}", line_nr + 1))?;
+        }
+        Ok(())
+    }
+}
+
+/// Patterns containing `*` or `?` are globs matched against the whole name, any other pattern matches
+/// names containing it.
+fn matches_filter(name: &str, pattern: &str) -> bool {
+    fn glob(name: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| glob(&name[skip..], rest)),
+            Some((&p, rest)) => name
+                .split_first()
+                .is_some_and(|(&c, name)| (p == '?' || p == c) && glob(name, rest)),
+        }
+    }
+
+    if pattern.contains(['*', '?']) {
+        let name: Vec<char> = name.chars().collect();
+        let pattern: Vec<char> = pattern.chars().collect();
+        glob(&name, &pattern)
+    } else {
+        name.contains(pattern)
+    }
+}
+
+// Options are applied in order, so arguments after --config override the config file.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<RunOptions, String> {
+    let mut options = RunOptions::default();
+
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {arg}"))?;
+        match arg.as_str() {
+            "--config" => {
+                let content = std::fs::read_to_string(&value)
+                    .map_err(|e| format!("Failed to read config {value}: {e}"))?;
+                options
+                    .apply_config(&content)
+                    .map_err(|e| format!("Invalid config {value}: {e}"))?;
+            }
+            _ => match arg.strip_prefix("--") {
+                Some(key) => options.set(&key.replace('-', "_"), &value)?,
+                None => return Err(format!("Unknown argument {arg}")),
+            },
+        }
+    }
+
+    Ok(options)
+}
+
 #[derive(Debug)]
 enum SolutionType {
     Better,
@@ -119,93 +285,566 @@ enum SolutionType {
 #[derive(Debug)]
 struct SolutionResult {
     solution_type: SolutionType,
+    /// None if no valid solution was found.
+    found_dist: Option<f64>,
+    best_known_dist: f64,
     duration_secs: f64,
 }
-fn main() {
-    init_logger();
 
-    let best_knowns = read_all_instances(PathBuf::from(
-        "instances/SolomonPotvinBengio/best_known.txt",
-    ));
+impl SolutionResult {
+    /// How much longer the found tour is than the best known one in percent, negative if it is shorter.
+    fn gap_percent(&self) -> Option<f64> {
+        self.found_dist
+            .map(|dist| (dist - self.best_known_dist) / self.best_known_dist * 100.0)
+            .filter(|gap| gap.is_finite())
+    }
+}
 
-    info!("Read {} instances", best_knowns.len());
+/// Aggregate over all solved instances; the gaps only cover the instances with a solution.
+#[derive(Debug, Default, PartialEq)]
+struct RunSummary {
+    better: usize,
+    equal: usize,
+    worse: usize,
+    not_found: usize,
+    mean_gap_percent: Option<f64>,
+    median_gap_percent: Option<f64>,
+    /// Wall time of solving all instances, less than the sum of their durations if they run in parallel.
+    total_secs: f64,
+}
 
-    let mut solution_results = HashMap::<String, SolutionResult>::new();
+impl RunSummary {
+    fn of(results: &[(&String, &SolutionResult)], total_secs: f64) -> Self {
+        let mut summary = RunSummary {
+            total_secs,
+            ..Default::default()
+        };
+        for (_, result) in results {
+            match result.solution_type {
+                SolutionType::Better => summary.better += 1,
+                SolutionType::Equal => summary.equal += 1,
+                SolutionType::Worse => summary.worse += 1,
+                SolutionType::NotFound => summary.not_found += 1,
+            }
+        }
+        let mut gaps: Vec<f64> = results
+            .iter()
+            .filter_map(|(_, result)| result.gap_percent())
+            .collect();
+        if !gaps.is_empty() {
+            gaps.sort_by(f64::total_cmp);
+            let middle = gaps.len() / 2;
+            summary.mean_gap_percent = Some(gaps.iter().sum::<f64>() / gaps.len() as f64);
+            summary.median_gap_percent = Some(if gaps.len().is_multiple_of(2) {
+                (gaps[middle - 1] + gaps[middle]) / 2.0
+            } else {
+                gaps[middle]
+            });
+        }
+        summary
+    }
+}
 
-    for best in best_knowns {
-        info!("Going to solve {}..", &best.name);
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Better: {}, equal: {}, worse: {}, not found: {}, mean gap: {}, median gap: {}, total time: {:.2}s",
+            self.better,
+            self.equal,
+            self.worse,
+            self.not_found,
+            format_gap(self.mean_gap_percent),
+            format_gap(self.median_gap_percent),
+            self.total_secs
+        )
+    }
+}
 
-        let start_time = std::time::Instant::now();
+/// Signed, so that gaps better than the best known stand out, or - if there is no gap.
+fn format_gap(gap: Option<f64>) -> String {
+    match gap {
+        // keeps rounding noise from showing up as -0.00%
+        Some(gap) if gap.abs() < 0.005 => "0.00%".to_string(),
+        Some(gap) => format!("{gap:+.2}%"),
+        None => "-".to_string(),
+    }
+}
 
-        let result = solve_tsp(
-            best.solution.get_instance().clone(),
-            beamsearch::Params {
-                beam_width: 1e7 as usize,
-                prune_similars: true,
-            },
-        );
+fn json_number(value: Option<f64>) -> String {
+    value
+        .filter(|value| value.is_finite())
+        .map_or("null".to_string(), |value| value.to_string())
+}
 
-        let duration_secs = start_time.elapsed().as_secs_f64();
-
-        if let Some(sol) = result {
-            let solution_type = if sol.get_time_distance().dist
-                < best.solution.get_time_distance().dist - 0.01
-            {
-                info!("FOUND BETTER SOLUTION THAN BEST KNOWN!");
-                SolutionType::Better
-            } else if sol.get_time_distance().dist > best.solution.get_time_distance().dist + 0.01 {
-                info!("Worse solution found.");
-                SolutionType::Worse
-            } else {
-                info!("Equally good solution found.");
-                SolutionType::Equal
-            };
-
-            solution_results.insert(
-                best.name.clone(),
-                SolutionResult {
-                    solution_type,
-                    duration_secs,
-                },
-            );
+// Quotes and escapes the value as a JSON string, including control characters.
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// Quotes the value if it contains a separator, quote or line break, doubling the quotes inside.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
-            info!(
-                "Found solution for {} with distance {} compared to {} in best known. (took {:.2}s)",
-                &best.name,
-                sol.get_time_distance().dist,
-                best.solution.get_time_distance().dist,
-                duration_secs
+fn results_to_json(results: &[(&String, &SolutionResult)], summary: &RunSummary) -> String {
+    let instances: Vec<String> = results
+        .iter()
+        .map(|(name, result)| {
+            format!(
+                r#"{{"name":{},"found_dist":{},"best_known_dist":{},"gap_percent":{},"solution_type":"{:?}","duration_secs":{}}}"#,
+                json_string(name),
+                json_number(result.found_dist),
+                json_number(Some(result.best_known_dist)),
+                json_number(result.gap_percent()),
+                result.solution_type,
+                result.duration_secs
             )
-        } else {
-            info!("Did not find a valid solution.");
-
-            solution_results.insert(
-                best.name,
-                SolutionResult {
-                    solution_type: SolutionType::NotFound,
-                    duration_secs,
-                },
-            );
+        })
+        .collect();
+
+    format!(
+        r#"{{"instances":[{}],"summary":{{"better":{},"equal":{},"worse":{},"not_found":{},"mean_gap_percent":{},"median_gap_percent":{},"total_secs":{}}}}}"#,
+        instances.join(","),
+        summary.better,
+        summary.equal,
+        summary.worse,
+        summary.not_found,
+        json_number(summary.mean_gap_percent),
+        json_number(summary.median_gap_percent),
+        summary.total_secs
+    )
+}
+
+/// One row per instance; the fields of instances without a solution are left empty.
+fn results_to_csv(results: &[(&String, &SolutionResult)]) -> String {
+    let optional = |value: Option<f64>| value.map_or(String::new(), |value| format!("{value:.2}"));
+    let mut csv =
+        String::from("name,found_dist,best_known_dist,gap_percent,solution_type,duration_secs\n");
+    for (name, result) in results {
+        csv.push_str(&format!(
+            "{},{},{:.2},{},{:?},{:.2}\n",
+            csv_field(name),
+            optional(result.found_dist),
+            result.best_known_dist,
+            optional(result.gap_percent()),
+            result.solution_type,
+            result.duration_secs
+        ));
+    }
+    csv
+}
+
+fn solve_instance(best: &BestKnown, options: &RunOptions) -> SolutionResult {
+    info!("Going to solve {}..", &best.name);
+
+    let start_time = std::time::Instant::now();
+
+    let result = solve_tsp(
+        best.solution.get_instance().clone(),
+        beamsearch::Params {
+            beam_width: options.beam_width,
+            prune_similars: options.prune_similars,
+            time_limit: options.time_limit,
+            prune_by_incumbent: true,
+            ..Default::default()
+        },
+    );
+
+    let duration_secs = start_time.elapsed().as_secs_f64();
+    let best_known_dist = best.solution.get_time_distance().dist;
+
+    let Some(sol) = result else {
+        info!("Did not find a valid solution for {}.", &best.name);
+        return SolutionResult {
+            solution_type: SolutionType::NotFound,
+            found_dist: None,
+            best_known_dist,
+            duration_secs,
+        };
+    };
+
+    let solution_type = if sol.get_time_distance().dist < best_known_dist - 0.01 {
+        info!("FOUND BETTER SOLUTION THAN BEST KNOWN FOR {}!", &best.name);
+        SolutionType::Better
+    } else if sol.get_time_distance().dist > best_known_dist + 0.01 {
+        info!("Worse solution found for {}.", &best.name);
+        SolutionType::Worse
+    } else {
+        info!("Equally good solution found for {}.", &best.name);
+        SolutionType::Equal
+    };
+
+    info!(
+        "Found solution for {} with distance {} compared to {} in best known. (took {:.2}s)",
+        &best.name,
+        sol.get_time_distance().dist,
+        best_known_dist,
+        duration_secs
+    );
+
+    SolutionResult {
+        solution_type,
+        found_dist: Some(sol.get_time_distance().dist),
+        best_known_dist,
+        duration_secs,
+    }
+}
+/// Maps the items on `concurrency` threads of their own, each taking the next item as soon as it is done with the
+/// previous one, and returns the results in the order of the items. Parallel work started by `f` runs on the
+/// global rayon pool, so that an item is neither limited to nor interleaved with the others on a pool of
+/// `concurrency` threads.
+fn map_concurrently<T, R, F>(items: &[T], concurrency: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..concurrency.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            return results;
+                        };
+                        results.push((index, f(item)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Worker thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(2);
         }
+    };
+
+    init_logger();
+
+    let mut best_knowns = read_all_instances(
+        &options.instances_dir,
+        options.best_known_file(),
+        options.distance_rounding,
+    );
+
+    info!("Read {} instances", best_knowns.len());
+
+    if let Some(filter) = &options.filter {
+        best_knowns.retain(|best| matches_filter(&best.name, filter));
+        info!("{} instances match filter {filter}", best_knowns.len());
     }
 
-    // Write results to last_result.txt
+    let run_start = std::time::Instant::now();
+    let solution_results: HashMap<String, SolutionResult> =
+        map_concurrently(&best_knowns, options.parallel_instances, |best| {
+            (best.name.clone(), solve_instance(best, &options))
+        })
+        .into_iter()
+        .collect();
+
+    // Write results to the output file
     let mut result_content = String::new();
 
     // Collect into vector and sort by name
     let mut sorted_results: Vec<(&String, &SolutionResult)> = solution_results.iter().collect();
     sorted_results.sort_by_key(|(name, _)| *name);
 
-    for (name, solution_result) in sorted_results {
+    for (name, solution_result) in &sorted_results {
         result_content.push_str(&format!(
-            "{:<15} {:>8} {:>8.2}s\n",
+            "{:<15} {:>8} {:>8} {:>8.2}s\n",
             name, 
             format!("{:?}", solution_result.solution_type), 
+            format_gap(solution_result.gap_percent()),
             solution_result.duration_secs
         ));
     }
 
-    std::fs::write("last_result.txt", &result_content).expect("Failed to write last_result.txt");
+    let summary = RunSummary::of(&sorted_results, run_start.elapsed().as_secs_f64());
+    result_content.push_str(&format!("{summary}\n"));
+
+    let write = |path: &PathBuf, content: &str| {
+        std::fs::write(path, content)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", path.display()))
+    };
+    write(&options.output, &result_content);
+    if let Some(path) = &options.json_output {
+        write(path, &results_to_json(&sorted_results, &summary));
+    }
+    if let Some(path) = &options.csv_output {
+        write(path, &results_to_csv(&sorted_results));
+    }
 
     info!("\n{result_content}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<RunOptions, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_keep_defaults() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(options, RunOptions::default());
+        assert_eq!(
+            options.best_known_file(),
+            PathBuf::from("instances/SolomonPotvinBengio/best_known.txt")
+        );
+    }
+
+    #[test]
+    fn arguments_override_defaults() {
+        let options = parse(&[
+            "--beam-width",
+            "50",
+            "--prune-similars",
+            "false",
+            "--instances-dir",
+            "other",
+            "--output",
+            "out.txt",
+            "--json-output",
+            "results.json",
+            "--distance-rounding",
+            "truncate-one-decimal",
+        ])
+        .unwrap();
+
+        assert_eq!(options.beam_width, 50);
+        assert!(!options.prune_similars);
+        assert_eq!(
+            options.best_known_file(),
+            PathBuf::from("other/best_known.txt")
+        );
+        assert_eq!(options.output, PathBuf::from("out.txt"));
+        assert_eq!(options.json_output, Some(PathBuf::from("results.json")));
+        assert_eq!(options.csv_output, None);
+        assert_eq!(
+            options.distance_rounding,
+            DistanceRounding::TruncateOneDecimal
+        );
+        assert!(parse(&["--distance-rounding", "floor"]).is_err());
+    }
+
+    #[test]
+    fn distance_rounding_is_applied_to_read_instances() {
+        // rc_201.1 is listed first, with a published distance of 444.54
+        let dir = PathBuf::from("instances/SolomonPotvinBengio");
+        let first_dist = |rounding| {
+            read_all_instances(&dir, dir.join("best_known.txt"), rounding)[0]
+                .solution
+                .get_time_distance()
+                .dist
+        };
+
+        // the bundled matrices follow the two decimal convention, truncating them loses 0.64
+        assert!((first_dist(DistanceRounding::None) - 444.54).abs() < 0.01);
+        assert!((first_dist(DistanceRounding::RoundTwoDecimals) - 444.54).abs() < 0.1);
+        assert!((first_dist(DistanceRounding::TruncateOneDecimal) - 443.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn config_file_is_overridden_by_later_arguments() {
+        let mut options = RunOptions::default();
+        options
+            .apply_config(
+                "# benchmark run\nbeam_width = 1000\ntime_limit = 2.5\n\ninstances_dir = \"data\" # quoted\n",
+            )
+            .unwrap();
+
+        assert_eq!(options.beam_width, 1000);
+        assert_eq!(options.time_limit, Some(Duration::from_millis(2500)));
+        assert_eq!(options.instances_dir, PathBuf::from("data"));
+        assert!(options.prune_similars);
+
+        let path = std::env::temp_dir().join(format!("rsopt_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "beam_width = 1000\nprune_similars = false\n").unwrap();
+        let options = parse(&["--config", path.to_str().unwrap(), "--beam-width", "7"]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(options.beam_width, 7);
+        assert!(!options.prune_similars);
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let mut options = RunOptions::default();
+
+        assert!(options.apply_config("beam_width 10").is_err());
+        assert!(options.apply_config("beam_width = wide").is_err());
+        assert!(options.apply_config("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn filter_matches_substrings_and_globs() {
+        assert!(matches_filter("rc_201.1.txt", "rc_201.1"));
+        assert!(matches_filter("rc_201.1.txt", "rc_20?.*"));
+        assert!(matches_filter("rc_201.1.txt", "*.1.txt"));
+        assert!(!matches_filter("rc_201.1.txt", "rc_202"));
+        assert!(!matches_filter("rc_201.1.txt", "*.2.txt"));
+        assert!(matches_filter("rc_201.1.txt", "rc_201*"));
+    }
+
+    fn result(solution_type: SolutionType, found_dist: Option<f64>) -> SolutionResult {
+        SolutionResult {
+            solution_type,
+            found_dist,
+            best_known_dist: 200.0,
+            duration_secs: 1.5,
+        }
+    }
+
+    #[test]
+    fn map_concurrently_limits_items_in_flight_and_keeps_order() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let items: Vec<usize> = (0..20).collect();
+
+        let results = map_concurrently(&items, 3, |&item| {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(now, Ordering::SeqCst);
+            // nested parallel work is not confined to a pool of 3 threads
+            let nr_threads = rayon::current_num_threads();
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            (item * 2, nr_threads)
+        });
+
+        assert_eq!(
+            results.iter().map(|(doubled, _)| *doubled).collect::<Vec<_>>(),
+            (0..20).map(|item| item * 2).collect::<Vec<_>>()
+        );
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 3);
+        assert!(
+            results
+                .iter()
+                .all(|(_, nr_threads)| *nr_threads == rayon::current_num_threads())
+        );
+    }
+
+    #[test]
+    fn names_are_escaped_in_json_and_quoted_in_csv() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        let name = "tab\tand\u{1f}";
+        assert_eq!(serde_json::from_str::<String>(&json_string(name)).unwrap(), name);
+        assert_eq!(csv_field("rc_201.1.txt"), "rc_201.1.txt");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn summary_aggregates_results() {
+        let names = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let results = [
+            result(SolutionType::Equal, Some(200.0)),
+            result(SolutionType::Worse, Some(210.0)),
+            result(SolutionType::NotFound, None),
+        ];
+        let results: Vec<_> = names.iter().zip(&results).collect();
+
+        // the instances took 4.5s in sum, but ran in parallel
+        let summary = RunSummary::of(&results, 2.0);
+
+        assert_eq!(
+            summary,
+            RunSummary {
+                better: 0,
+                equal: 1,
+                worse: 1,
+                not_found: 1,
+                mean_gap_percent: Some(2.5),
+                median_gap_percent: Some(2.5),
+                total_secs: 2.0,
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "Better: 0, equal: 1, worse: 1, not found: 1, mean gap: +2.50%, median gap: +2.50%, total time: 2.00s"
+        );
+    }
+
+    #[test]
+    fn median_gap_is_robust_to_outliers() {
+        let names = ["a", "b", "c", "d"].map(String::from);
+        let results = [
+            result(SolutionType::Better, Some(198.0)),
+            result(SolutionType::Equal, Some(200.0)),
+            result(SolutionType::Worse, Some(202.0)),
+            result(SolutionType::Worse, Some(400.0)),
+        ];
+        let results: Vec<_> = names.iter().zip(&results).collect();
+
+        let summary = RunSummary::of(&results, 0.0);
+
+        assert_eq!(summary.mean_gap_percent, Some(25.0));
+        assert_eq!(summary.median_gap_percent, Some(0.5));
+        assert_eq!(format_gap(results[0].1.gap_percent()), "-1.00%");
+        assert_eq!(format_gap(results[1].1.gap_percent()), "0.00%");
+        assert_eq!(format_gap(Some(-1e-9)), "0.00%");
+        assert_eq!(format_gap(None), "-");
+    }
+
+    #[test]
+    fn results_are_written_as_json_and_csv() {
+        let names = ["rc_201.1.txt".to_string(), "rc_202.1.txt".to_string()];
+        let results = [
+            result(SolutionType::Worse, Some(210.0)),
+            result(SolutionType::NotFound, None),
+        ];
+        let results: Vec<_> = names.iter().zip(&results).collect();
+
+        assert_eq!(
+            results_to_json(&results, &RunSummary::of(&results, 2.0)),
+            r#"{"instances":[{"name":"rc_201.1.txt","found_dist":210,"best_known_dist":200,"gap_percent":5,"solution_type":"Worse","duration_secs":1.5},{"name":"rc_202.1.txt","found_dist":null,"best_known_dist":200,"gap_percent":null,"solution_type":"NotFound","duration_secs":1.5}],"summary":{"better":0,"equal":0,"worse":1,"not_found":1,"mean_gap_percent":5,"median_gap_percent":5,"total_secs":2}}"#
+        );
+        assert_eq!(
+            results_to_csv(&results),
+            "name,found_dist,best_known_dist,gap_percent,solution_type,duration_secs\n\
+             rc_201.1.txt,210.00,200.00,5.00,Worse,1.50\n\
+             rc_202.1.txt,,200.00,,NotFound,1.50\n"
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(parse(&["--beam-width"]).is_err());
+        assert!(parse(&["--beam-width", "wide"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
@@ -1,8 +1,25 @@
+mod tsp_exact;
+mod tsp_heuristics;
 mod tsp_instance;
+mod tsp_opt_tour;
 mod tsp_solution;
 mod tsp_solver;
 mod tsp_utility;
 
-pub use tsp_instance::TSPInstance;
-pub use tsp_solution::{TSPSolution, TimeDist};
-pub use tsp_solver::solve_tsp;
+pub use tsp_exact::solve_tsp_exact;
+pub use tsp_heuristics::clarke_wright;
+pub use tsp_instance::{
+    DistanceMetric, DistanceRounding, EuclideanMetric, InfeasibilityReason, InstanceLayout,
+    InstanceParseError, TSPInstance, validate_instance_dir,
+};
+pub use tsp_opt_tour::read_opt_tour;
+pub use tsp_solution::{
+    RankedViolation, StopRecord, TSPSolution, TimeDist, ValidationError, WindowViolation,
+};
+#[cfg(feature = "serde")]
+pub use tsp_solution::SolutionDto;
+pub use tsp_solver::{
+    FrontierSnapshots, MultiSeedResult, MultiStartResult, PruningImpact, TSPNode, build_node_chain,
+    minimal_feasible_width, next_city_regret, pruning_impact, solve_tsp, solve_tsp_k,
+    solve_tsp_multiseed, solve_tsp_multistart, solve_tsp_pareto, solve_tsp_with_snapshots,
+};
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::{TSPInstance, TSPSolution};
+
+/// A partial path from the depot, stored in an arena and linked to its predecessor.
+struct Label {
+    city: usize,
+    time: f64,
+    dist: f64,
+    parent: Option<usize>,
+}
+
+/// Keeps only the labels that are not dominated by another one, i.e. reached neither earlier nor shorter.
+/// Both criteria are needed, since a longer path that arrives earlier can still meet windows a shorter one misses.
+fn insert_pareto(front: &mut Vec<usize>, labels: &[Label], new: usize) -> bool {
+    let dominates = |a: &Label, b: &Label| a.time <= b.time && a.dist <= b.dist;
+    if front
+        .iter()
+        .any(|&old| dominates(&labels[old], &labels[new]))
+    {
+        return false;
+    }
+    front.retain(|&old| !dominates(&labels[new], &labels[old]));
+    front.push(new);
+    true
+}
+
+/// Solves the instance to optimality with the Held-Karp dynamic program over (visited cities, last city), keeping
+/// all Pareto-optimal (arrival time, distance) pairs per state to respect the windows. Precedences, pinned
+/// positions and open tours are respected as in the beam search. Optional cities are free to skip, since only the
+/// distance is minimized, so the result can differ from a beam search with a skip penalty. The effort grows
+/// exponentially with the number of cities, so this is meant for instances of up to about 18 cities, e.g. to
+/// validate the beam search.
+/// Returns None if no valid tour exists or the instance has more than 64 cities.
+pub fn solve_tsp_exact(instance: &Arc<TSPInstance>) -> Option<TSPSolution> {
+    let n = instance.len();
+    // the visited cities of a state are a bitmask
+    if n > 64 {
+        return None;
+    }
+    let depot = instance.depot();
+    if n == 1 {
+        let solution = TSPSolution::new(instance.clone(), vec![depot]);
+        return solution.is_valid().then_some(solution);
+    }
+
+    let bit = |city: usize| 1u64 << city;
+    let mandatory = (0..n)
+        .filter(|&city| !instance.is_optional(city))
+        .fold(bit(depot), |mask, city| mask | bit(city));
+
+    let mut labels = vec![Label {
+        city: depot,
+        time: instance.window_of(depot).0,
+        dist: 0.0,
+        parent: None,
+    }];
+    let mut layer: BTreeMap<(u64, usize), Vec<usize>> =
+        BTreeMap::from([((bit(depot), depot), vec![0])]);
+    let mut best: Option<usize> = None;
+
+    for position in 1..=n {
+        let mut next_layer: BTreeMap<(u64, usize), Vec<usize>> = BTreeMap::new();
+        for (&(mask, _), front) in &layer {
+            let complete = mask & mandatory == mandatory;
+            // open tours end wherever every mandatory city is visited
+            if complete && instance.is_open_tour() {
+                for &label in front {
+                    if best.is_none_or(|best| labels[label].dist < labels[best].dist) {
+                        best = Some(label);
+                    }
+                }
+            }
+            for &from in front {
+                for to in 0..n {
+                    let returns = to == depot;
+                    if (returns && (!complete || instance.is_open_tour()))
+                        || (!returns && mask & bit(to) != 0)
+                        || instance.is_forbidden(labels[from].city, to)
+                        || !instance.allows_city_at(to, position)
+                        || !instance.predecessors_visited(to, |city| mask & bit(city) != 0)
+                    {
+                        continue;
+                    }
+
+                    let label = &labels[from];
+                    let reached = label.time
+                        + instance.service_time_of(label.city)
+                        + instance.dist_from_to(label.city, to);
+                    let (window_start, window_end) = instance.window_of(to);
+                    if reached > window_end {
+                        continue;
+                    }
+                    let dist = label.dist + instance.dist_from_to(label.city, to);
+                    labels.push(Label {
+                        city: to,
+                        time: reached.max(window_start),
+                        dist,
+                        parent: Some(from),
+                    });
+                    let new = labels.len() - 1;
+
+                    if returns {
+                        if best.is_none_or(|best| dist < labels[best].dist) {
+                            best = Some(new);
+                        }
+                    } else if !insert_pareto(
+                        next_layer.entry((mask | bit(to), to)).or_default(),
+                        &labels,
+                        new,
+                    ) {
+                        labels.pop();
+                    }
+                }
+            }
+        }
+        layer = next_layer;
+    }
+
+    let best = best?;
+    let mut path: Vec<usize> = std::iter::successors(Some(best), |&label| labels[label].parent)
+        .map(|label| labels[label].city)
+        .collect();
+    path.reverse();
+    let solution = TSPSolution::new(instance.clone(), path);
+    debug_assert!(solution.is_valid());
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::beamsearch::Params;
+    use super::super::solve_tsp;
+    use super::*;
+
+    #[test]
+    fn matches_beam_search_on_test_instance() {
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1000.0, 1.0],
+                vec![1000.0, 0.0, 1000.0],
+                vec![1000.0, 100.0, 0.0],
+            ],
+            vec![(0.0, 1200.0), (200.0, 2000.0), (5.0, 2000.0)],
+        ));
+
+        let exact = solve_tsp_exact(&instance).unwrap();
+        let beam = solve_tsp(instance, Params::default()).unwrap();
+
+        assert_eq!(*exact.get_path(), vec![0, 2, 1, 0]);
+        assert_eq!(exact.get_path(), beam.get_path());
+    }
+
+    #[test]
+    fn beats_or_ties_narrow_beam_on_random_instances() {
+        for seed in 0..5 {
+            let instance = Arc::new(TSPInstance::random(9, 100.0, 0.5, seed));
+
+            let exact = solve_tsp_exact(&instance).unwrap();
+            let beam = solve_tsp(
+                instance,
+                Params {
+                    beam_width: 3,
+                    ..Default::default()
+                },
+            );
+
+            assert!(exact.is_valid());
+            if let Some(beam) = beam {
+                assert!(exact.get_time_distance().dist <= beam.get_time_distance().dist + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_infeasible_instance() {
+        // 1 has to be reached by time 1 and 2 by time 2, but they are 2 apart
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        ));
+
+        assert!(solve_tsp_exact(&instance).is_none());
+    }
+
+    #[test]
+    fn returns_none_for_more_than_64_cities() {
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            (0..65).map(|i| (i as f64, 0.0)).collect(),
+            vec![(0.0, 1000.0); 65],
+        ));
+
+        assert!(solve_tsp_exact(&instance).is_none());
+    }
+}
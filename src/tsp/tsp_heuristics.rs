@@ -0,0 +1,523 @@
+use std::sync::Arc;
+
+use super::{TSPInstance, TSPSolution};
+
+// The path serving the segment from the depot, returning to it unless the instance has open tours.
+fn path_from_depot(instance: &TSPInstance, segment: &[usize]) -> Vec<usize> {
+    let depot = instance.depot();
+    let return_to_depot = (!instance.is_open_tour()).then_some(depot);
+    std::iter::once(depot)
+        .chain(segment.iter().copied())
+        .chain(return_to_depot)
+        .collect()
+}
+
+// A segment can only be part of a feasible tour if it is feasible when served directly from the depot,
+// since any other predecessor makes it start later.
+fn is_feasible_from_depot(instance: &Arc<TSPInstance>, segment: &[usize]) -> bool {
+    TSPSolution::new(instance.clone(), path_from_depot(instance, segment))
+        .slacks()
+        .iter()
+        .all(|slack| *slack >= 0.0)
+}
+
+fn segment_dist(instance: &TSPInstance, segment: &[usize]) -> f64 {
+    segment
+        .windows(2)
+        .map(|pair| instance.dist_from_to(pair[0], pair[1]))
+        .sum()
+}
+
+/// Clarke-Wright savings heuristic for a single tour: starts with one trip from the depot per city and merges
+/// the trips at their ends in order of decreasing savings, as long as the merged trip stays window-feasible.
+/// The trips of open tours do not return to the depot. Returns None if the trips cannot be merged into one
+/// feasible tour.
+pub fn clarke_wright(instance: &Arc<TSPInstance>) -> Option<TSPSolution> {
+    let n = instance.len();
+    let depot = instance.depot();
+    if n == 1 {
+        return Some(TSPSolution::new(instance.clone(), vec![depot]));
+    }
+
+    let mut segments: Vec<Option<Vec<usize>>> = (0..n)
+        .map(|city| (city != depot).then(|| vec![city]))
+        .collect();
+    let mut segment_of: Vec<usize> = (0..n).collect();
+
+    for (i, j, _) in instance.savings_matrix() {
+        let (a, b) = (segment_of[i], segment_of[j]);
+        if a == b {
+            continue;
+        }
+        let (Some(seg_a), Some(seg_b)) = (&segments[a], &segments[b]) else {
+            continue;
+        };
+
+        // i and j have to be adjacent in the merged segment, so both must be at an end of their segment
+        let orientations = |seg: &Vec<usize>| {
+            let mut reversed = seg.clone();
+            reversed.reverse();
+            [seg.clone(), reversed]
+        };
+        let mut candidates = Vec::new();
+        for oriented_a in orientations(seg_a) {
+            for oriented_b in orientations(seg_b) {
+                if oriented_a.last() == Some(&i) && oriented_b.first() == Some(&j) {
+                    candidates.push([oriented_a.as_slice(), oriented_b.as_slice()].concat());
+                }
+                if oriented_b.last() == Some(&j) && oriented_a.first() == Some(&i) {
+                    candidates.push([oriented_b.as_slice(), oriented_a.as_slice()].concat());
+                }
+            }
+        }
+
+        let merged = candidates
+            .into_iter()
+            .filter(|candidate| is_feasible_from_depot(instance, candidate))
+            .min_by(|x, y| {
+                let with_depot =
+                    |seg: &Vec<usize>| segment_dist(instance, &path_from_depot(instance, seg));
+                with_depot(x).total_cmp(&with_depot(y))
+            });
+
+        if let Some(merged) = merged {
+            for &city in &merged {
+                segment_of[city] = a;
+            }
+            segments[a] = Some(merged);
+            segments[b] = None;
+        }
+    }
+
+    let mut remaining = segments.into_iter().flatten();
+    let tour = remaining.next()?;
+    if remaining.next().is_some() {
+        return None;
+    }
+
+    let solution = TSPSolution::new(instance.clone(), path_from_depot(instance, &tour));
+    solution.is_valid().then_some(solution)
+}
+
+impl TSPSolution {
+    /// Builds a tour from `start` back to it by always moving on to the closest unvisited city whose window can
+    /// still be reached, respecting precedences and pinned positions. Open tours end at the last city instead.
+    /// Optional cities are left out if none of them is reachable anymore. Returns None if the greedy tour gets
+    /// stuck or ends up invalid.
+    pub fn nearest_neighbor(instance: &Arc<TSPInstance>, start: usize) -> Option<TSPSolution> {
+        let mut path = vec![start];
+        let mut visited = vec![false; instance.len()];
+        visited[start] = true;
+        let mut time = instance.window_of(start).0;
+
+        loop {
+            let current = *path.last().unwrap();
+            let departure = time + instance.service_time_of(current);
+            let next = (0..instance.len())
+                .filter(|&city| !visited[city])
+                .filter(|&city| !instance.is_forbidden(current, city))
+                .filter(|&city| instance.allows_city_at(city, path.len()))
+                .filter(|&city| instance.predecessors_visited(city, |other| visited[other]))
+                .filter(|&city| {
+                    departure + instance.dist_from_to(current, city) <= instance.window_of(city).1
+                })
+                .min_by(|&a, &b| {
+                    instance
+                        .dist_from_to(current, a)
+                        .total_cmp(&instance.dist_from_to(current, b))
+                });
+            let Some(next) = next else {
+                break;
+            };
+
+            time =
+                (departure + instance.dist_from_to(current, next)).max(instance.window_of(next).0);
+            visited[next] = true;
+            path.push(next);
+        }
+
+        if instance.len() > 1 && !instance.is_open_tour() {
+            path.push(start);
+        }
+        let solution = TSPSolution::new(instance.clone(), path);
+        solution.is_valid().then_some(solution)
+    }
+
+    /// Repeatedly reverses segments of the path as long as a reversal keeps the tour valid and makes it shorter.
+    /// Stops at a local optimum, so the result is never longer than this solution.
+    pub fn two_opt_improve(&self) -> TSPSolution {
+        let instance = self.get_instance();
+        let mut path = self.get_path().clone();
+        let mut dist = self.get_time_distance().dist;
+
+        let mut improved = true;
+        while improved {
+            improved = false;
+            // the depot at the start, and at the end of a roundtrip, stays in place
+            let end = if instance.is_open_tour() {
+                path.len()
+            } else {
+                path.len() - 1
+            };
+            for i in 1..end.saturating_sub(1) {
+                for j in i + 1..end {
+                    let mut candidate = path.clone();
+                    candidate[i..=j].reverse();
+                    let candidate = TSPSolution::new(instance.clone(), candidate);
+                    let candidate_dist = candidate.get_time_distance().dist;
+                    if candidate_dist < dist - 1e-9 && candidate.is_valid() {
+                        path = candidate.get_path().clone();
+                        dist = candidate_dist;
+                        improved = true;
+                    }
+                }
+            }
+        }
+
+        TSPSolution::new(instance.clone(), path)
+    }
+
+    /// Repeatedly moves a run of `segment_len` consecutive cities to another position of the path as long as
+    /// the move keeps the tour valid and makes it shorter.
+    pub fn or_opt_improve(&self, segment_len: usize) -> TSPSolution {
+        let instance = self.get_instance();
+        let mut path = self.get_path().clone();
+        let mut dist = self.get_time_distance().dist;
+        if segment_len == 0 {
+            return TSPSolution::new(instance.clone(), path);
+        }
+
+        let mut improved = true;
+        while improved {
+            improved = false;
+            // runs are taken from and moved to after the depot at the start, and before it at the end of a
+            // roundtrip
+            let end = if instance.is_open_tour() {
+                path.len()
+            } else {
+                path.len() - 1
+            };
+            let last_start = (end + 1).saturating_sub(segment_len);
+            for start in 1..last_start {
+                let mut rest = path.clone();
+                let segment: Vec<usize> = rest.drain(start..start + segment_len).collect();
+                for insert_at in (1..last_start).filter(|&insert_at| insert_at != start) {
+                    let mut candidate = rest.clone();
+                    candidate.splice(insert_at..insert_at, segment.iter().copied());
+                    let candidate = TSPSolution::new(instance.clone(), candidate);
+                    let candidate_dist = candidate.get_time_distance().dist;
+                    if candidate_dist < dist - 1e-9 && candidate.is_valid() {
+                        path = candidate.get_path().clone();
+                        dist = candidate_dist;
+                        improved = true;
+                        break;
+                    }
+                }
+                if improved {
+                    break;
+                }
+            }
+        }
+
+        TSPSolution::new(instance.clone(), path)
+    }
+
+    /// Alternates 2-opt and or-opt with runs of 1 to 3 cities until neither shortens the tour anymore.
+    pub fn local_search(&self) -> TSPSolution {
+        let mut solution = self.two_opt_improve();
+        loop {
+            let dist = solution.get_time_distance().dist;
+            for segment_len in 1..=3 {
+                solution = solution.or_opt_improve(segment_len);
+            }
+            solution = solution.two_opt_improve();
+            if solution.get_time_distance().dist >= dist - 1e-9 {
+                return solution;
+            }
+        }
+    }
+
+    /// Reorders the cities of a tour that misses windows until it is valid again, without changing which cities
+    /// are visited. First every late city is swapped forward step by step, then growing suffixes starting at the
+    /// first late city are sorted by window end. Returns None if neither leads to a valid tour.
+    pub fn repair(&self) -> Option<TSPSolution> {
+        let instance = self.get_instance();
+        let path = self.get_path();
+        let candidate = TSPSolution::new(instance.clone(), path.clone());
+        if candidate.is_valid() {
+            return Some(candidate);
+        }
+        if path.len() < 3 {
+            return None;
+        }
+        // the depot at the start, and at the end of a roundtrip, stays in place
+        let end = if instance.is_open_tour() {
+            path.len()
+        } else {
+            path.len() - 1
+        };
+
+        let mut swapped = path.clone();
+        for _ in 0..path.len() * path.len() {
+            let candidate = TSPSolution::new(instance.clone(), swapped.clone());
+            if candidate.is_valid() {
+                return Some(candidate);
+            }
+            match candidate.window_violations().first() {
+                Some(violation) if (2..end).contains(&violation.step) => {
+                    swapped.swap(violation.step - 1, violation.step)
+                }
+                _ => break,
+            }
+        }
+
+        let first_late = self
+            .window_violations()
+            .first()
+            .map_or(end - 1, |violation| violation.step.clamp(1, end - 1));
+        (1..=first_late).rev().find_map(|from| {
+            let mut sorted = path.clone();
+            sorted[from..end]
+                .sort_by(|&a, &b| instance.window_of(a).1.total_cmp(&instance.window_of(b).1));
+            let candidate = TSPSolution::new(instance.clone(), sorted);
+            candidate.is_valid().then_some(candidate)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::beamsearch::Params;
+    use super::super::solve_tsp;
+    use super::*;
+
+    #[test]
+    fn clarke_wright_beats_nearest_neighbor_on_clusters() {
+        // nearest neighbor crosses the depot twice (0, 1, -2, -8, 5, 0), costing 28 instead of 26
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-2.0, 0.0), (5.0, 0.0), (-8.0, 0.0)],
+            vec![(0.0, 1000.0); 5],
+        ));
+
+        let solution = clarke_wright(&instance).unwrap();
+
+        assert!(solution.is_valid());
+        let nearest_neighbor = TSPSolution::nearest_neighbor(&instance, 0).unwrap();
+        assert_eq!(nearest_neighbor.get_time_distance().dist, 28.0);
+        assert_eq!(solution.get_time_distance().dist, 26.0);
+    }
+
+    #[test]
+    fn clarke_wright_keeps_windows_feasible() {
+        // 0 -> 1 -> 2 -> 0 would save more, but 2 closes before 1 opens
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 2.0, 2.0],
+                vec![2.0, 0.0, 1.0],
+                vec![2.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 100.0), (10.0, 100.0), (0.0, 5.0)],
+        ));
+
+        let solution = clarke_wright(&instance).unwrap();
+
+        assert_eq!(*solution.get_path(), vec![0, 2, 1, 0]);
+    }
+
+    #[test]
+    fn clarke_wright_builds_open_tour() {
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)],
+                vec![(0.0, 1000.0); 4],
+            )
+            .with_open_tour(true),
+        );
+
+        let solution = clarke_wright(&instance).unwrap();
+
+        assert!(solution.is_valid());
+        assert_eq!(*solution.get_path(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn nearest_neighbor_leaves_out_optional_city_whose_window_has_closed() {
+        // the optional 3 is closest, but cannot be reached in time from anywhere
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (2.0, 0.0), (-3.0, 0.0), (1.0, 0.0)],
+                vec![(0.0, 100.0), (0.0, 100.0), (0.0, 100.0), (0.0, 0.5)],
+            )
+            .with_optional_cities(vec![false, false, false, true]),
+        );
+
+        let solution = TSPSolution::nearest_neighbor(&instance, 0).unwrap();
+
+        assert!(solution.is_valid());
+        assert_eq!(*solution.get_path(), vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn nearest_neighbor_is_valid_on_solomon_instance() {
+        let instance = Arc::new(
+            TSPInstance::from_file("instances/SolomonPotvinBengio/rc_206.1.txt".into()).unwrap(),
+        );
+
+        let solution = TSPSolution::nearest_neighbor(&instance, instance.depot());
+
+        assert!(solution.unwrap().is_valid());
+    }
+
+    #[test]
+    fn nearest_neighbor_returns_none_when_greedy_gets_stuck() {
+        // 1 and 2 are both reachable first, but not one after the other
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        ));
+
+        assert!(TSPSolution::nearest_neighbor(&instance, 0).is_none());
+    }
+
+    #[test]
+    fn two_opt_removes_crossing() {
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0); 4],
+        ));
+        let crossing = TSPSolution::new(instance, vec![0, 2, 1, 3, 0]);
+
+        let improved = crossing.two_opt_improve();
+
+        assert!(improved.is_valid());
+        assert_eq!(improved.get_time_distance().dist, 4.0);
+    }
+
+    #[test]
+    fn two_opt_never_returns_invalid_or_longer_tour() {
+        // the crossing tour is forced by the windows: 2 has to be served before 1
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0), (5.0, 1000.0), (0.0, 2.0), (0.0, 1000.0)],
+        ));
+        let forced = TSPSolution::new(instance, vec![0, 2, 1, 3, 0]);
+        assert!(forced.is_valid());
+
+        let improved = forced.two_opt_improve();
+
+        assert!(improved.is_valid());
+        assert!(improved.get_time_distance().dist <= forced.get_time_distance().dist);
+        assert!(
+            improved.get_path().iter().position(|&c| c == 2)
+                < improved.get_path().iter().position(|&c| c == 1)
+        );
+    }
+
+    #[test]
+    fn or_opt_moves_city_to_its_neighbors() {
+        // 3 lies between 1 and 2 on the line, but is visited last
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (3.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 1000.0); 4],
+        ));
+        let detour = TSPSolution::new(instance, vec![0, 1, 2, 3, 0]);
+
+        let improved = detour.or_opt_improve(1);
+
+        assert!(improved.is_valid());
+        assert_eq!(improved.get_time_distance().dist, 6.0);
+    }
+
+    #[test]
+    fn two_opt_reverses_end_of_open_tour() {
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)],
+                vec![(0.0, 1000.0); 4],
+            )
+            .with_open_tour(true),
+        );
+        let detour = TSPSolution::new(instance, vec![0, 1, 3, 2]);
+
+        let improved = detour.two_opt_improve();
+
+        assert!(improved.is_valid());
+        assert_eq!(*improved.get_path(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn or_opt_moves_city_to_end_of_open_tour() {
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)],
+                vec![(0.0, 1000.0); 4],
+            )
+            .with_open_tour(true),
+        );
+        let detour = TSPSolution::new(instance, vec![0, 3, 1, 2]);
+
+        let improved = detour.or_opt_improve(1);
+
+        assert!(improved.is_valid());
+        assert_eq!(*improved.get_path(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn repair_restores_feasible_order() {
+        // on the line, 1 closes first and 3 last, so only 0, 1, 2, 3, 0 meets all windows
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0), (0.0, 3.0)],
+        ));
+
+        for path in [
+            vec![0, 3, 2, 1, 0],
+            vec![0, 2, 1, 3, 0],
+            vec![0, 3, 1, 2, 0],
+        ] {
+            let broken = TSPSolution::new(instance.clone(), path);
+            assert!(!broken.is_valid());
+
+            let repaired = broken.repair().unwrap();
+
+            assert!(repaired.is_valid());
+            assert_eq!(*repaired.get_path(), vec![0, 1, 2, 3, 0]);
+        }
+    }
+
+    #[test]
+    fn repair_returns_none_for_infeasible_instance() {
+        // 1 has to be reached by time 1 and 2 by time 2, but they are 2 apart
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        ));
+
+        assert!(
+            TSPSolution::new(instance, vec![0, 2, 1, 0])
+                .repair()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn local_search_does_not_worsen_beam_result_on_solomon_instance() {
+        let instance = Arc::new(
+            TSPInstance::from_file("instances/SolomonPotvinBengio/rc_201.1.txt".into()).unwrap(),
+        );
+        let beam = solve_tsp(
+            instance,
+            Params {
+                beam_width: 5,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let improved = beam.local_search();
+
+        assert!(improved.is_valid());
+        assert!(improved.get_time_distance().dist <= beam.get_time_distance().dist);
+    }
+}
@@ -1,10 +1,200 @@
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use std::{fmt, path::PathBuf};
 
+use super::tsp_utility::{min_cost_assignment, symmetric_eigen};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstanceLayout {
+    /// Every distance row contains all num_cities entries.
+    Full,
+    /// Row i contains only the entries from column i onwards; the matrix is mirrored to be symmetric.
+    UpperTriangular,
+}
+
+/// Rounding convention applied to every distance, since published best-knowns differ in how they round.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DistanceRounding {
+    #[default]
+    None,
+    TruncateOneDecimal,
+    RoundTwoDecimals,
+}
+
+impl DistanceRounding {
+    pub fn apply(self, dist: f64) -> f64 {
+        // the epsilon keeps values like 0.3 * 10 = 2.9999999999999996 from being truncated to 2
+        match self {
+            DistanceRounding::None => dist,
+            DistanceRounding::TruncateOneDecimal => (dist * 10.0 + 1e-9).trunc() / 10.0,
+            DistanceRounding::RoundTwoDecimals => (dist * 100.0).round() / 100.0,
+        }
+    }
+}
+
+/// Tolerance for floating point errors when checking distances for symmetry and the triangle inequality.
+const METRIC_EPSILON: f64 = 1e-9;
+
+/// Computes distances on demand, for instances too large to store all pairwise distances.
+pub trait DistanceMetric: Send + Sync + fmt::Debug {
+    fn distance(&self, from: usize, to: usize) -> f64;
+}
+
+/// Euclidean distances between points in the plane.
+#[derive(Debug, Clone)]
+pub struct EuclideanMetric {
+    coordinates: Vec<(f64, f64)>,
+}
+
+impl EuclideanMetric {
+    pub fn new(coordinates: Vec<(f64, f64)>) -> Self {
+        Self { coordinates }
+    }
+}
+
+impl DistanceMetric for EuclideanMetric {
+    fn distance(&self, from: usize, to: usize) -> f64 {
+        let (x1, y1) = self.coordinates[from];
+        let (x2, y2) = self.coordinates[to];
+        ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+    }
+}
+
+#[derive(Debug)]
+pub enum InstanceParseError {
+    Io(std::io::Error),
+    Empty,
+    InvalidNumber {
+        line: usize,
+        token: String,
+    },
+    MissingLines {
+        section: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    WrongNumberOfEntries {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    MissingKeyword(&'static str),
+    Unsupported(String),
+    InvalidCity {
+        line: usize,
+        city: usize,
+    },
+}
+
+impl fmt::Display for InstanceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceParseError::Io(e) => write!(f, "could not read file: {e}"),
+            InstanceParseError::Empty => write!(f, "file is empty"),
+            InstanceParseError::InvalidNumber { line, token } => {
+                write!(f, "line {line}: could not convert {token} to a number")
+            }
+            InstanceParseError::MissingLines {
+                section,
+                expected,
+                found,
+            } => write!(f, "expected {expected} lines of {section}, found {found}"),
+            InstanceParseError::WrongNumberOfEntries {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} entries, found {found}"),
+            InstanceParseError::MissingKeyword(keyword) => write!(f, "missing {keyword}"),
+            InstanceParseError::Unsupported(what) => write!(f, "unsupported {what}"),
+            InstanceParseError::InvalidCity { line, city } => {
+                write!(f, "line {line}: city {city} is out of range")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstanceParseError {}
+
+/// An obvious contradiction in an instance that no tour can satisfy, see `TSPInstance::feasibility_precheck`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InfeasibilityReason {
+    PrecedenceCycle,
+    /// The window of the city closes before it can be reached from the depot.
+    UnreachableCity {
+        city: usize,
+    },
+    /// After serving the city, the depot cannot be reached before its window closes.
+    NoReturn {
+        city: usize,
+    },
+    /// Neither order of the two cities allowed by the precedences meets both windows.
+    ConflictingWindows {
+        first: usize,
+        second: usize,
+    },
+}
+
+impl fmt::Display for InfeasibilityReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InfeasibilityReason::PrecedenceCycle => {
+                write!(f, "precedence constraints contain a cycle")
+            }
+            InfeasibilityReason::UnreachableCity { city } => {
+                write!(f, "city {city} cannot be reached before its window closes")
+            }
+            InfeasibilityReason::NoReturn { city } => {
+                write!(f, "the depot cannot be reached in time after city {city}")
+            }
+            InfeasibilityReason::ConflictingWindows { first, second } => {
+                write!(
+                    f,
+                    "cities {first} and {second} cannot both be served in time"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for InfeasibilityReason {}
+
+/// Tries to parse every file in the directory, sorted by file name, without solving anything.
+pub fn validate_instance_dir(dir: PathBuf) -> Vec<(String, Result<(), InstanceParseError>)> {
+    let entries = std::fs::read_dir(&dir)
+        .unwrap_or_else(|_| panic!("Failed to read directory {}", dir.display()));
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, TSPInstance::from_file(path).map(|_| ()))
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct TSPInstance {
     num_cities: usize,
-    distances: Vec<Vec<f64>>,
+    // row-major, the distance from a to b is at a * num_cities + b. Empty if the metric is set.
+    distances: Vec<f64>,
+    metric: Option<Box<dyn DistanceMetric>>,
     time_windows: Vec<(f64, f64)>,
+    service_times: Vec<f64>,
+    optional: Vec<bool>,
+    precedences: Vec<(usize, usize)>,
+    pinned_positions: Vec<(usize, usize)>,
+    depot: usize,
+    coordinates: Option<Vec<(f64, f64)>>,
+    rounding: DistanceRounding,
+    // edges longer than this, in particular infinite ones, cannot be traveled
+    forbidden_threshold: f64,
+    open_tour: bool,
 }
 
 impl TSPInstance {
@@ -15,60 +205,742 @@ impl TSPInstance {
         }
         assert!(time_windows.len() == num_cities);
 
+        Self::with_distances(
+            num_cities,
+            distances.into_iter().flatten().collect(),
+            None,
+            time_windows,
+        )
+    }
+
+    /// Computes the distances with the metric whenever they are needed instead of storing them.
+    pub fn from_metric(
+        metric: impl DistanceMetric + 'static,
+        time_windows: Vec<(f64, f64)>,
+    ) -> Self {
+        Self::with_distances(
+            time_windows.len(),
+            Vec::new(),
+            Some(Box::new(metric)),
+            time_windows,
+        )
+    }
+
+    fn with_distances(
+        num_cities: usize,
+        distances: Vec<f64>,
+        metric: Option<Box<dyn DistanceMetric>>,
+        time_windows: Vec<(f64, f64)>,
+    ) -> Self {
         TSPInstance {
             num_cities,
             distances,
+            metric,
             time_windows,
+            service_times: vec![0.0; num_cities],
+            optional: vec![false; num_cities],
+            precedences: Vec::new(),
+            pinned_positions: Vec::new(),
+            depot: 0,
+            coordinates: None,
+            rounding: DistanceRounding::None,
+            forbidden_threshold: f64::MAX,
+            open_tour: false,
+        }
+    }
+
+    pub fn with_distance_rounding(mut self, rounding: DistanceRounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    pub fn distance_rounding(&self) -> DistanceRounding {
+        self.rounding
+    }
+
+    /// Forbids traveling along edges longer than the threshold, in addition to the always forbidden infinite ones.
+    pub fn with_forbidden_threshold(mut self, threshold: f64) -> Self {
+        self.forbidden_threshold = threshold;
+        self
+    }
+
+    /// Whether the solver may not travel directly from one city to the other.
+    pub fn is_forbidden(&self, from: usize, to: usize) -> bool {
+        self.dist_from_to(from, to) > self.forbidden_threshold
+    }
+
+    /// Builds the symmetric matrix of Euclidean distances between the coordinates, which are kept as well.
+    pub fn from_coordinates(coords: Vec<(f64, f64)>, time_windows: Vec<(f64, f64)>) -> Self {
+        assert!(coords.len() == time_windows.len());
+
+        let distances = Self::euclidean_distances(&coords);
+        let mut instance = TSPInstance::new(coords.len(), distances, time_windows);
+        instance.coordinates = Some(coords);
+        instance
+    }
+
+    /// Scatters the cities uniformly in the square [0, area]^2 and builds windows around the arrival times of a
+    /// random hidden tour, so that the instance is always feasible. A window tightness of 0 lets every window
+    /// span the whole tour duration, 1 shrinks them to the hidden arrival times. Windows are widened to integer
+    /// bounds. The coordinates are kept, see `coordinates`.
+    pub fn random(num_cities: usize, area: f64, window_tightness: f64, seed: u64) -> Self {
+        assert!(num_cities > 0);
+        assert!((0.0..=1.0).contains(&window_tightness));
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let coords: Vec<(f64, f64)> = (0..num_cities)
+            .map(|_| (rng.random_range(0.0..=area), rng.random_range(0.0..=area)))
+            .collect();
+
+        let mut hidden_tour: Vec<usize> = (1..num_cities).collect();
+        hidden_tour.shuffle(&mut rng);
+
+        let dist = |a: usize, b: usize| {
+            let ((x1, y1), (x2, y2)) = (coords[a], coords[b]);
+            ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+        };
+        let mut arrivals = vec![0.0; num_cities];
+        let mut time = 0.0;
+        let mut last = 0;
+        for &city in &hidden_tour {
+            time += dist(last, city);
+            arrivals[city] = time;
+            last = city;
+        }
+        let return_time = time + dist(last, 0);
+
+        let half_width = (1.0 - window_tightness) * return_time;
+        let time_windows = (0..num_cities)
+            .map(|city| {
+                if city == 0 {
+                    (0.0, (return_time + half_width).ceil())
+                } else {
+                    let arrival = arrivals[city];
+                    (
+                        (arrival - half_width).max(0.0).floor(),
+                        (arrival + half_width).ceil(),
+                    )
+                }
+            })
+            .collect();
+
+        Self::from_coordinates(coords, time_windows)
+    }
+
+    fn euclidean_distances(coords: &[(f64, f64)]) -> Vec<Vec<f64>> {
+        coords
+            .iter()
+            .map(|(x1, y1)| {
+                coords
+                    .iter()
+                    .map(|(x2, y2)| ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The coordinates of the cities, if the instance was built from them.
+    pub fn coordinates(&self) -> Option<&Vec<(f64, f64)>> {
+        self.coordinates.as_ref()
+    }
+
+    /// Time that has to be spent at each city before it can be left again. Defaults to zero everywhere.
+    pub fn with_service_times(mut self, service_times: Vec<f64>) -> Self {
+        assert!(service_times.len() == self.num_cities);
+        self.service_times = service_times;
+        self
+    }
+
+    /// Approximate 2D coordinates reproducing the distances, found by classical multidimensional scaling.
+    /// Only meant for plotting: for non-Euclidean matrices the result does not match the distances exactly.
+    pub fn embed_2d(&self) -> Vec<(f64, f64)> {
+        let n = self.num_cities;
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let squared: Vec<Vec<f64>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| ((self.dist_from_to(i, j) + self.dist_from_to(j, i)) / 2.0).powi(2))
+                    .collect()
+            })
+            .collect();
+        let row_means: Vec<f64> = squared
+            .iter()
+            .map(|row| row.iter().sum::<f64>() / n as f64)
+            .collect();
+        let total_mean = row_means.iter().sum::<f64>() / n as f64;
+
+        // double centering; the matrix is symmetric, so row and column means coincide
+        let gram: Vec<Vec<f64>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| -0.5 * (squared[i][j] - row_means[i] - row_means[j] + total_mean))
+                    .collect()
+            })
+            .collect();
+
+        let (values, vectors) = symmetric_eigen(&gram);
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+        let axis = |rank: usize, city: usize| {
+            order
+                .get(rank)
+                .map_or(0.0, |&k| values[k].max(0.0).sqrt() * vectors[city][k])
+        };
+
+        (0..n).map(|city| (axis(0, city), axis(1, city))).collect()
+    }
+
+    /// Marks cities that a tour may leave out. The depot is always mandatory.
+    pub fn with_optional_cities(mut self, optional: Vec<bool>) -> Self {
+        assert!(optional.len() == self.num_cities);
+        assert!(
+            !optional.get(self.depot).copied().unwrap_or(false),
+            "the depot cannot be optional"
+        );
+        self.optional = optional;
+        self
+    }
+
+    pub fn is_optional(&self, city: usize) -> bool {
+        assert!(city < self.num_cities);
+        self.optional[city]
+    }
+
+    /// Each pair (a, b) requires city a to be visited before city b.
+    pub fn with_precedences(mut self, precedences: Vec<(usize, usize)>) -> Self {
+        for &(before, after) in &precedences {
+            assert!(before < self.num_cities && after < self.num_cities);
         }
+        self.precedences = precedences;
+        self
     }
 
-    // First line of each file contains the number of cities.
+    pub fn precedences(&self) -> &Vec<(usize, usize)> {
+        &self.precedences
+    }
+
+    /// Whether every city required to come before the given city is already visited.
+    pub fn predecessors_visited(&self, city: usize, is_visited: impl Fn(usize) -> bool) -> bool {
+        self.precedences
+            .iter()
+            .filter(|&&(_, after)| after == city)
+            .all(|&(before, _)| is_visited(before))
+    }
+
+    /// Makes tours end at the last visited city instead of returning to the depot. Such a tour is complete as soon
+    /// as it has visited every mandatory city.
+    pub fn with_open_tour(mut self, open_tour: bool) -> Self {
+        self.open_tour = open_tour;
+        self
+    }
+
+    pub fn is_open_tour(&self) -> bool {
+        self.open_tour
+    }
+
+    /// Makes tours start and end at the given city instead of city 0.
+    pub fn with_depot(mut self, depot: usize) -> Self {
+        assert!(depot < self.num_cities);
+        assert!(!self.optional[depot], "the depot cannot be optional");
+        assert!(
+            self.pinned_positions.iter().all(|&(_, city)| city != depot),
+            "the depot cannot be pinned"
+        );
+        self.depot = depot;
+        self
+    }
+
+    /// The city all tours start and end at.
+    pub fn depot(&self) -> usize {
+        self.depot
+    }
+
+    /// Each pair (position, city) requires the city to be at that index of the path, where the depot is at 0.
+    pub fn with_pinned_positions(mut self, pinned_positions: Vec<(usize, usize)>) -> Self {
+        for (i, &(position, city)) in pinned_positions.iter().enumerate() {
+            assert!(
+                (1..self.num_cities).contains(&position)
+                    && city < self.num_cities
+                    && city != self.depot,
+                "only non-depot cities can be pinned to interior positions, got city {city} at position {position}"
+            );
+            for &(other_position, other_city) in &pinned_positions[..i] {
+                assert!(
+                    other_position != position,
+                    "cities {other_city} and {city} are both pinned to position {position}"
+                );
+                assert!(
+                    other_city != city,
+                    "city {city} is pinned to positions {other_position} and {position}"
+                );
+            }
+        }
+        self.pinned_positions = pinned_positions;
+        self
+    }
+
+    pub fn pinned_positions(&self) -> &Vec<(usize, usize)> {
+        &self.pinned_positions
+    }
+
+    /// Whether the city may be placed at the given index of the path without breaking a pinned position.
+    pub fn allows_city_at(&self, city: usize, position: usize) -> bool {
+        self.pinned_positions
+            .iter()
+            .all(|&(pinned_position, pinned_city)| {
+                (pinned_position == position) == (pinned_city == city)
+            })
+    }
+
+    /// Groups of at least two non-depot cities that can be swapped in any tour without changing its cost or
+    /// feasibility: identical windows, service times and optional flags, identical distances to and from all other
+    /// cities, and not involved in any precedence or pinned position.
+    pub fn equivalent_city_classes(&self) -> Vec<Vec<usize>> {
+        let is_constrained = |city: usize| {
+            self.precedences
+                .iter()
+                .any(|&(before, after)| before == city || after == city)
+                || self
+                    .pinned_positions
+                    .iter()
+                    .any(|&(_, pinned)| pinned == city)
+        };
+
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        for city in (0..self.num_cities).filter(|&city| city != self.depot && !is_constrained(city))
+        {
+            match classes
+                .iter_mut()
+                .find(|class| self.are_interchangeable(class[0], city))
+            {
+                Some(class) => class.push(city),
+                None => classes.push(vec![city]),
+            }
+        }
+
+        classes.retain(|class| class.len() > 1);
+        classes
+    }
+
+    fn are_interchangeable(&self, a: usize, b: usize) -> bool {
+        self.time_windows[a] == self.time_windows[b]
+            && self.service_times[a] == self.service_times[b]
+            && self.optional[a] == self.optional[b]
+            && self.dist_from_to(a, b) == self.dist_from_to(b, a)
+            && (0..self.num_cities)
+                .filter(|&other| other != a && other != b)
+                .all(|other| {
+                    self.dist_from_to(a, other) == self.dist_from_to(b, other)
+                        && self.dist_from_to(other, a) == self.dist_from_to(other, b)
+                })
+    }
+
+    // Lower bound on when the city can be served: going there directly from the depot, waiting if necessary.
+    fn earliest_service_start(&self, city: usize) -> f64 {
+        let depot = self.depot;
+        let direct =
+            self.window_of(depot).0 + self.service_time_of(depot) + self.dist_from_to(depot, city);
+        direct.max(self.window_of(city).0)
+    }
+
+    /// Mandatory cities whose window cannot be met by any tour: they cannot be reached in time even directly
+    /// from the depot, the depot cannot be reached in time afterwards, or a city required before them
+    /// cannot be served early enough. Relaxing their windows is necessary for feasibility.
+    pub fn infeasibility_witnesses(&self) -> Vec<usize> {
+        let depot_end = self.window_of(self.depot).1;
+        (0..self.num_cities)
+            .filter(|&city| city != self.depot && !self.is_optional(city))
+            .filter(|&city| {
+                let start = self.earliest_service_start(city);
+                let unreachable = start > self.window_of(city).1;
+                let no_return =
+                    start + self.service_time_of(city) + self.dist_from_to(city, self.depot)
+                        > depot_end;
+                let blocked_by_predecessor = self.precedences.iter().any(|&(before, after)| {
+                    after == city
+                        && self.earliest_service_start(before)
+                            + self.service_time_of(before)
+                            + self.dist_from_to(before, city)
+                            > self.window_of(city).1
+                });
+                unreachable || no_return || blocked_by_predecessor
+            })
+            .collect()
+    }
+
+    /// Cheap necessary conditions for a tour meeting all hard windows, reporting the first one that fails. Only
+    /// mandatory cities are checked, and travel times are bounded from below by shortest paths, ignoring the
+    /// service times and waiting on the way, so that an instance passing the check may still be infeasible.
+    pub fn feasibility_precheck(&self) -> Result<(), InfeasibilityReason> {
+        if self.precedence_has_cycle() {
+            return Err(InfeasibilityReason::PrecedenceCycle);
+        }
+
+        let depot = self.depot;
+        let mandatory: Vec<usize> = (0..self.num_cities)
+            .filter(|&city| city != depot && !self.is_optional(city))
+            .collect();
+        let from_depot = self.shortest_dists(depot, false);
+        let to_depot = self.shortest_dists(depot, true);
+        let earliest_start: Vec<f64> = (0..self.num_cities)
+            .map(|city| {
+                let reached =
+                    self.window_of(depot).0 + self.service_time_of(depot) + from_depot[city];
+                reached.max(self.window_of(city).0)
+            })
+            .collect();
+
+        for &city in &mandatory {
+            if earliest_start[city] > self.window_of(city).1 {
+                return Err(InfeasibilityReason::UnreachableCity { city });
+            }
+            if !self.open_tour
+                && earliest_start[city] + self.service_time_of(city) + to_depot[city]
+                    > self.window_of(depot).1
+            {
+                return Err(InfeasibilityReason::NoReturn { city });
+            }
+        }
+
+        // a path between two cities either is the direct edge or leaves the first and enters the second
+        // through two distinct edges
+        let n = self.num_cities;
+        let edge = |from: usize, to: usize| {
+            if from == to || self.is_forbidden(from, to) {
+                f64::INFINITY
+            } else {
+                self.dist_from_to(from, to)
+            }
+        };
+        let min_outgoing: Vec<f64> = (0..n)
+            .map(|from| {
+                (0..n)
+                    .map(|to| edge(from, to))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+        let min_incoming: Vec<f64> = (0..n)
+            .map(|to| {
+                (0..n)
+                    .map(|from| edge(from, to))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+        let min_travel =
+            |from: usize, to: usize| edge(from, to).min(min_outgoing[from] + min_incoming[to]);
+        let order_possible = |first: usize, second: usize| {
+            !self.precedences.contains(&(second, first))
+                && earliest_start[first] + self.service_time_of(first) + min_travel(first, second)
+                    <= self.window_of(second).1
+        };
+
+        for (i, &first) in mandatory.iter().enumerate() {
+            for &second in &mandatory[i + 1..] {
+                if !order_possible(first, second) && !order_possible(second, first) {
+                    return Err(InfeasibilityReason::ConflictingWindows { first, second });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Dijkstra on the dense distance matrix, skipping forbidden edges. If reverse is set, the distances
+    // are the ones towards the source instead of from it.
+    fn shortest_dists(&self, source: usize, reverse: bool) -> Vec<f64> {
+        let n = self.num_cities;
+        let edge = |a: usize, b: usize| {
+            let (from, to) = if reverse { (b, a) } else { (a, b) };
+            if self.is_forbidden(from, to) {
+                f64::INFINITY
+            } else {
+                self.dist_from_to(from, to)
+            }
+        };
+
+        let mut dists = vec![f64::INFINITY; n];
+        let mut done = vec![false; n];
+        dists[source] = 0.0;
+        while let Some(current) = (0..n)
+            .filter(|&city| !done[city] && dists[city].is_finite())
+            .min_by(|&a, &b| dists[a].total_cmp(&dists[b]))
+        {
+            done[current] = true;
+            for next in (0..n).filter(|&next| !done[next]) {
+                dists[next] = dists[next].min(dists[current] + edge(current, next));
+            }
+        }
+        dists
+    }
+
+    /// Kahn's topological sort over the precedence graph: if not every city can be sorted, there is a cycle.
+    pub fn precedence_has_cycle(&self) -> bool {
+        let mut in_degree = vec![0usize; self.num_cities];
+        let mut successors = vec![Vec::new(); self.num_cities];
+        for &(before, after) in &self.precedences {
+            in_degree[after] += 1;
+            successors[before].push(after);
+        }
+
+        let mut ready: Vec<usize> = (0..self.num_cities)
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut nr_sorted = 0;
+        while let Some(city) = ready.pop() {
+            nr_sorted += 1;
+            for &next in &successors[city] {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+
+        nr_sorted < self.num_cities
+    }
+
+    // First line of each file contains the number of cities. Blank lines and lines starting with # are ignored.
     // The next num_cities lines contain the distance matrix, with each line containing num_cities floating-point numbers.
     // The next num_cities lines contain the time windows, with each line containing two floating-point numbers.
-    pub fn from_file(path: PathBuf) -> Self {
-        let content = std::fs::read_to_string(&path)
-            .unwrap_or_else(|_| panic!("Failed to read file {}", path.display()));
+    pub fn from_file(path: PathBuf) -> Result<Self, InstanceParseError> {
+        Self::from_file_with_layout(path, InstanceLayout::Full)
+    }
 
-        assert!(!content.is_empty());
+    pub fn from_file_with_layout(
+        path: PathBuf,
+        layout: InstanceLayout,
+    ) -> Result<Self, InstanceParseError> {
+        let content = std::fs::read_to_string(&path).map_err(InstanceParseError::Io)?;
 
-        let mut lines = content.lines();
+        Self::parse(&content, layout)
+    }
 
-        let num_cities_str = lines.next().unwrap();
-        let num_cities: usize = num_cities_str
-            .parse()
-            .unwrap_or_else(|_| panic!("Could not convert {} to usize!", num_cities_str));
+    // the distance before rounding
+    fn raw_distance(&self, from: usize, to: usize) -> f64 {
+        match &self.metric {
+            Some(metric) => metric.distance(from, to),
+            None => self.distances[from * self.num_cities + to],
+        }
+    }
+
+    fn distance_rows(&self) -> impl Iterator<Item = Vec<f64>> {
+        (0..self.num_cities).map(|from| {
+            (0..self.num_cities)
+                .map(|to| self.raw_distance(from, to))
+                .collect()
+        })
+    }
+
+    /// Writes the instance in the full layout read by from_file. Only distances and time windows are kept.
+    pub fn to_file(&self, path: PathBuf) -> std::io::Result<()> {
+        let mut content = format!("{}\n", self.num_cities);
+        for row in self.distance_rows() {
+            let entries: Vec<String> = row.iter().map(|d| format!("{d:.6}")).collect();
+            content.push_str(&entries.join(" "));
+            content.push('\n');
+        }
+        for (start, end) in &self.time_windows {
+            content.push_str(&format!("{start:.6} {end:.6}\n"));
+        }
+
+        std::fs::write(path, content)
+    }
 
-        let distance_lines_str: Vec<&str> = lines.by_ref().take(num_cities).collect();
-        assert!(distance_lines_str.len() == num_cities);
+    fn parse(content: &str, layout: InstanceLayout) -> Result<Self, InstanceParseError> {
+        // blank lines and lines starting with # do not count as data lines
+        let mut lines = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| {
+                let line = line.trim();
+                !line.is_empty() && !line.starts_with('#')
+            });
+
+        let (num_cities_line, num_cities_str) = lines.next().ok_or(InstanceParseError::Empty)?;
+        let num_cities: usize =
+            num_cities_str
+                .trim()
+                .parse()
+                .map_err(|_| InstanceParseError::InvalidNumber {
+                    line: num_cities_line,
+                    token: num_cities_str.to_string(),
+                })?;
+
+        let distance_lines: Vec<(usize, &str)> = lines.by_ref().take(num_cities).collect();
+        if distance_lines.len() != num_cities {
+            return Err(InstanceParseError::MissingLines {
+                section: "distances",
+                expected: num_cities,
+                found: distance_lines.len(),
+            });
+        }
 
         let mut distances: Vec<Vec<f64>> = Vec::with_capacity(num_cities);
-        for line in distance_lines_str {
-            let row_entries: Vec<f64> = line
-                .split_whitespace()
-                .map(|x| x.parse().unwrap())
-                .collect();
+        for (row, (line_nr, line)) in distance_lines.into_iter().enumerate() {
+            let row_entries = Self::parse_numbers(line_nr, line)?;
+            let expected = match layout {
+                InstanceLayout::Full => num_cities,
+                InstanceLayout::UpperTriangular => num_cities - row,
+            };
+            if row_entries.len() != expected {
+                return Err(InstanceParseError::WrongNumberOfEntries {
+                    line: line_nr,
+                    expected,
+                    found: row_entries.len(),
+                });
+            }
             distances.push(row_entries);
         }
 
-        let time_windows_str: Vec<&str> = lines.take(num_cities).collect();
-        assert!(time_windows_str.len() == num_cities);
+        if layout == InstanceLayout::UpperTriangular {
+            distances = Self::mirror_upper_triangle(distances);
+        }
+
+        let time_window_lines: Vec<(usize, &str)> = lines.take(num_cities).collect();
+        if time_window_lines.len() != num_cities {
+            return Err(InstanceParseError::MissingLines {
+                section: "time windows",
+                expected: num_cities,
+                found: time_window_lines.len(),
+            });
+        }
+
+        let mut time_windows: Vec<(f64, f64)> = Vec::with_capacity(num_cities);
+        for (line_nr, line) in time_window_lines {
+            let split = Self::parse_numbers(line_nr, line)?;
+            if split.len() < 2 {
+                return Err(InstanceParseError::WrongNumberOfEntries {
+                    line: line_nr,
+                    expected: 2,
+                    found: split.len(),
+                });
+            }
+            time_windows.push((split[0], split[1]));
+        }
+
+        Ok(TSPInstance::new(num_cities, distances, time_windows))
+    }
+
+    // TSPLIB files consist of "KEY : VALUE" header lines followed by NODE_COORD_SECTION and
+    // TIME_WINDOW_SECTION, whose lines contain a 1-based city id and two numbers each.
+    pub fn from_tsplib(path: PathBuf) -> Result<Self, InstanceParseError> {
+        let content = std::fs::read_to_string(&path).map_err(InstanceParseError::Io)?;
+
+        Self::parse_tsplib(&content)
+    }
+
+    fn parse_tsplib(content: &str) -> Result<Self, InstanceParseError> {
+        let mut dimension: Option<usize> = None;
+        let mut coordinates: Vec<Option<(f64, f64)>> = Vec::new();
+        let mut time_windows: Vec<Option<(f64, f64)>> = Vec::new();
+        let mut section: Option<&str> = None;
+
+        for (i, line) in content.lines().enumerate() {
+            let line_nr = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line == "EOF" {
+                continue;
+            }
 
-        let time_windows: Vec<(f64, f64)> = time_windows_str
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "DIMENSION" => {
+                        let num_cities: usize =
+                            value
+                                .parse()
+                                .map_err(|_| InstanceParseError::InvalidNumber {
+                                    line: line_nr,
+                                    token: value.to_string(),
+                                })?;
+                        dimension = Some(num_cities);
+                        coordinates = vec![None; num_cities];
+                        time_windows = vec![None; num_cities];
+                    }
+                    "EDGE_WEIGHT_TYPE" if value != "EUC_2D" => {
+                        return Err(InstanceParseError::Unsupported(format!(
+                            "EDGE_WEIGHT_TYPE {value}"
+                        )));
+                    }
+                    _ => {}
+                }
+                section = None;
+                continue;
+            }
+
+            if line.ends_with("_SECTION") {
+                section = Some(line);
+                continue;
+            }
+
+            let target = match section {
+                Some("NODE_COORD_SECTION") => &mut coordinates,
+                Some("TIME_WINDOW_SECTION") => &mut time_windows,
+                _ => continue,
+            };
+            let numbers = Self::parse_numbers(line_nr, line)?;
+            if numbers.len() != 3 {
+                return Err(InstanceParseError::WrongNumberOfEntries {
+                    line: line_nr,
+                    expected: 3,
+                    found: numbers.len(),
+                });
+            }
+            let city = numbers[0] as usize;
+            if city == 0 || city > target.len() {
+                return Err(InstanceParseError::InvalidCity {
+                    line: line_nr,
+                    city,
+                });
+            }
+            target[city - 1] = Some((numbers[1], numbers[2]));
+        }
+
+        let num_cities = dimension.ok_or(InstanceParseError::MissingKeyword("DIMENSION"))?;
+        let coordinates: Vec<(f64, f64)> = coordinates.into_iter().collect::<Option<_>>().ok_or(
+            InstanceParseError::MissingKeyword("NODE_COORD_SECTION entry"),
+        )?;
+        let time_windows: Vec<(f64, f64)> = time_windows.into_iter().collect::<Option<_>>().ok_or(
+            InstanceParseError::MissingKeyword("TIME_WINDOW_SECTION entry"),
+        )?;
+
+        // EUC_2D distances are rounded to the nearest integer
+        let distances: Vec<Vec<f64>> = Self::euclidean_distances(&coordinates)
             .into_iter()
-            .map(|line| {
-                let split: Vec<f64> = line
-                    .split_whitespace()
-                    .map(|x| x.parse().unwrap())
-                    .collect();
-                (split[0], split[1])
-            })
+            .map(|row| row.into_iter().map(f64::round).collect())
             .collect();
 
-        TSPInstance {
-            num_cities,
-            distances,
-            time_windows,
+        let mut instance = TSPInstance::new(num_cities, distances, time_windows);
+        instance.coordinates = Some(coordinates);
+        Ok(instance)
+    }
+
+    fn parse_numbers(line_nr: usize, line: &str) -> Result<Vec<f64>, InstanceParseError> {
+        line.split_whitespace()
+            .map(|x| {
+                x.parse().map_err(|_| InstanceParseError::InvalidNumber {
+                    line: line_nr,
+                    token: x.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    // Row i of an upper triangle holds the entries (i, i)..(i, num_cities - 1).
+    fn mirror_upper_triangle(rows: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let num_cities = rows.len();
+        let mut distances = vec![vec![0.0; num_cities]; num_cities];
+        for (from, row) in rows.into_iter().enumerate() {
+            for (offset, dist) in row.into_iter().enumerate() {
+                let to = from + offset;
+                distances[from][to] = dist;
+                distances[to][from] = dist;
+            }
         }
+        distances
     }
 
     pub fn len(&self) -> usize {
@@ -77,7 +949,7 @@ impl TSPInstance {
 
     pub fn dist_from_to(&self, from: usize, to: usize) -> f64 {
         assert!(from < self.num_cities && to < self.num_cities);
-        self.distances[from][to]
+        self.rounding.apply(self.raw_distance(from, to))
     }
 
     pub fn window_of(&self, node: usize) -> (f64, f64) {
@@ -90,6 +962,111 @@ impl TSPInstance {
         self.time_windows[node]
     }
 
+    pub fn service_time_of(&self, node: usize) -> f64 {
+        assert!(node < self.num_cities);
+        self.service_times[node]
+    }
+
+    /// Whether the distance from i to j equals the one from j to i for all cities, up to `METRIC_EPSILON`.
+    pub fn is_symmetric(&self) -> bool {
+        (0..self.num_cities).all(|i| {
+            (i + 1..self.num_cities).all(|j| {
+                (self.dist_from_to(i, j) - self.dist_from_to(j, i)).abs() <= METRIC_EPSILON
+            })
+        })
+    }
+
+    /// Whether no detour via a third city is shorter than the direct edge by more than `METRIC_EPSILON`, which
+    /// bounds like the assignment lower bound rely on. Takes cubic time in the number of cities.
+    pub fn satisfies_triangle_inequality(&self) -> bool {
+        let n = self.num_cities;
+        (0..n).all(|i| {
+            (0..n).all(|j| {
+                (0..n).all(|via| {
+                    self.dist_from_to(i, j)
+                        <= self.dist_from_to(i, via) + self.dist_from_to(via, j) + METRIC_EPSILON
+                })
+            })
+        })
+    }
+
+    /// Mean distance over all edges between distinct cities, or 0 if there are none.
+    pub fn average_edge_length(&self) -> f64 {
+        if self.num_cities < 2 {
+            return 0.0;
+        }
+        let sum: f64 = (0..self.num_cities)
+            .flat_map(|from| (0..self.num_cities).map(move |to| (from, to)))
+            .filter(|(from, to)| from != to)
+            .map(|(from, to)| self.dist_from_to(from, to))
+            .sum();
+        sum / (self.num_cities * (self.num_cities - 1)) as f64
+    }
+
+    /// Clarke-Wright savings dist(depot, i) + dist(depot, j) - dist(i, j) of serving the non-depot cities i < j
+    /// in one route instead of two separate trips from the depot, sorted by decreasing savings.
+    pub fn savings_matrix(&self) -> Vec<(usize, usize, f64)> {
+        let depot = self.depot;
+        let mut savings: Vec<(usize, usize, f64)> = (0..self.num_cities)
+            .flat_map(|i| (i + 1..self.num_cities).map(move |j| (i, j)))
+            .filter(|&(i, j)| i != depot && j != depot)
+            .map(|(i, j)| {
+                let saving = self.dist_from_to(depot, i) + self.dist_from_to(depot, j)
+                    - self.dist_from_to(i, j);
+                (i, j, saving)
+            })
+            .collect();
+
+        savings.sort_by(|a, b| b.2.total_cmp(&a.2));
+        savings
+    }
+
+    /// Lower bound on the tour distance: every city picks a distinct successor other than itself at minimal
+    /// total cost, ignoring subtours and time windows.
+    pub fn assignment_lower_bound(&self) -> f64 {
+        if self.num_cities < 2 {
+            return 0.0;
+        }
+
+        // large enough to never be chosen over any real assignment
+        let forbidden: f64 = (0..self.num_cities)
+            .flat_map(|from| (0..self.num_cities).map(move |to| (from, to)))
+            .map(|(from, to)| self.raw_distance(from, to).abs())
+            .sum::<f64>()
+            + 1.0;
+        let cost: Vec<Vec<f64>> = (0..self.num_cities)
+            .map(|from| {
+                (0..self.num_cities)
+                    .map(|to| {
+                        if from == to {
+                            forbidden
+                        } else {
+                            self.dist_from_to(from, to)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        min_cost_assignment(&cost)
+    }
+
+    /// Width of each city's time window relative to the average edge length. Small values mark cities
+    /// whose windows strongly constrain the search.
+    pub fn window_tightness(&self) -> Vec<f64> {
+        let average_edge_length = self.average_edge_length();
+        self.time_windows
+            .iter()
+            .map(|(start, end)| {
+                if average_edge_length > 0.0 {
+                    (end - start) / average_edge_length
+                } else {
+                    f64::INFINITY
+                }
+            })
+            .collect()
+    }
+
     pub fn window_of_contains(&self, node: usize, time: f64) -> bool {
         assert!(node < self.num_cities);
         let (start, end) = self.window_of(node);
@@ -102,7 +1079,667 @@ impl fmt::Display for TSPInstance {
         write!(
             f,
             "Cities: {}\nDistances:{:?}\nTime_windows:{:?}",
-            self.num_cities, self.distances, self.time_windows
+            self.num_cities,
+            self.distance_rows().collect::<Vec<_>>(),
+            self.time_windows
         )
     }
 }
+
+// Serialized form of an instance. Instances computing their distances with a metric are stored with the full
+// matrix, since the metric itself cannot be serialized. Infinite distances, i.e. always forbidden edges, are stored
+// as None, since formats like JSON cannot represent them.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct InstanceData {
+    distances: Vec<Vec<Option<f64>>>,
+    time_windows: Vec<(f64, f64)>,
+    service_times: Vec<f64>,
+    optional: Vec<bool>,
+    precedences: Vec<(usize, usize)>,
+    pinned_positions: Vec<(usize, usize)>,
+    depot: usize,
+    coordinates: Option<Vec<(f64, f64)>>,
+    rounding: DistanceRounding,
+    forbidden_threshold: f64,
+    open_tour: bool,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TSPInstance {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InstanceData {
+            distances: self
+                .distance_rows()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|d| d.is_finite().then_some(d))
+                        .collect()
+                })
+                .collect(),
+            time_windows: self.time_windows.clone(),
+            service_times: self.service_times.clone(),
+            optional: self.optional.clone(),
+            precedences: self.precedences.clone(),
+            pinned_positions: self.pinned_positions.clone(),
+            depot: self.depot,
+            coordinates: self.coordinates.clone(),
+            rounding: self.rounding,
+            forbidden_threshold: self.forbidden_threshold,
+            open_tour: self.open_tour,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TSPInstance {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let data = InstanceData::deserialize(deserializer)?;
+        let n = data.time_windows.len();
+        let city_in_range = |&city: &usize| city < n;
+        if data.distances.len() != n || data.distances.iter().any(|row| row.len() != n) {
+            return Err(D::Error::custom(format!(
+                "expected a {n}x{n} distance matrix"
+            )));
+        }
+        if data.service_times.len() != n
+            || data.optional.len() != n
+            || data
+                .coordinates
+                .as_ref()
+                .is_some_and(|coords| coords.len() != n)
+        {
+            return Err(D::Error::custom(format!("expected {n} entries per city")));
+        }
+        if !city_in_range(&data.depot)
+            || !data
+                .precedences
+                .iter()
+                .all(|(before, after)| city_in_range(before) && city_in_range(after))
+            || !data
+                .pinned_positions
+                .iter()
+                .all(|&(position, city)| (1..n).contains(&position) && city_in_range(&city))
+        {
+            return Err(D::Error::custom(format!(
+                "city out of range for {n} cities"
+            )));
+        }
+
+        let mut instance = Self::with_distances(
+            n,
+            data.distances
+                .into_iter()
+                .flatten()
+                .map(|d| d.unwrap_or(f64::INFINITY))
+                .collect(),
+            None,
+            data.time_windows,
+        );
+        instance.service_times = data.service_times;
+        instance.optional = data.optional;
+        instance.precedences = data.precedences;
+        instance.pinned_positions = data.pinned_positions;
+        instance.depot = data.depot;
+        instance.coordinates = data.coordinates;
+        instance.rounding = data.rounding;
+        instance.forbidden_threshold = data.forbidden_threshold;
+        instance.open_tour = data.open_tour;
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upper_triangular_layout_reconstructs_full_matrix() {
+        let full = "3\n0 1 2\n1 0 3\n2 3 0\n0 100\n5 50\n10 60\n";
+        let triangular = "3\n0 1 2\n0 3\n0\n0 100\n5 50\n10 60\n";
+
+        let from_full = TSPInstance::parse(full, InstanceLayout::Full).unwrap();
+        let from_triangular =
+            TSPInstance::parse(triangular, InstanceLayout::UpperTriangular).unwrap();
+
+        assert_eq!(from_full.distances, from_triangular.distances);
+        assert_eq!(from_full.time_windows, from_triangular.time_windows);
+    }
+
+    fn create_four_city_instance() -> TSPInstance {
+        TSPInstance::new(4, vec![vec![0.0; 4]; 4], vec![(0.0, 100.0); 4])
+    }
+
+    #[test]
+    fn random_instance_is_reproducible_and_respects_tightness() {
+        let instance = TSPInstance::random(20, 100.0, 0.9, 3);
+        let again = TSPInstance::random(20, 100.0, 0.9, 3);
+
+        assert_eq!(instance.len(), 20);
+        assert_eq!(instance.time_windows, again.time_windows);
+        assert_eq!(instance.coordinates(), again.coordinates());
+        assert_ne!(
+            instance.time_windows,
+            TSPInstance::random(20, 100.0, 0.9, 4).time_windows
+        );
+        assert!(
+            instance
+                .coordinates()
+                .unwrap()
+                .iter()
+                .all(|&(x, y)| (0.0..=100.0).contains(&x) && (0.0..=100.0).contains(&y))
+        );
+
+        let loose = TSPInstance::random(20, 100.0, 0.0, 3);
+        let width = |instance: &TSPInstance, city| {
+            let (start, end) = instance.window_of(city);
+            end - start
+        };
+        assert!((1..20).all(|city| width(&instance, city) <= width(&loose, city)));
+    }
+
+    #[test]
+    fn euclidean_instance_is_metric() {
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (3.0, 4.0), (0.0, 1.5), (-2.0, 7.0)],
+            vec![(0.0, 100.0); 4],
+        );
+
+        assert!(instance.is_symmetric());
+        assert!(instance.satisfies_triangle_inequality());
+    }
+
+    #[test]
+    fn shortcut_and_asymmetry_are_detected() {
+        // going from 0 to 2 via 1 costs 2 instead of 10
+        let instance = TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 10.0],
+                vec![1.0, 0.0, 1.0],
+                vec![10.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 100.0); 3],
+        );
+        let asymmetric = TSPInstance::new(
+            2,
+            vec![vec![0.0, 1.0], vec![1.5, 0.0]],
+            vec![(0.0, 100.0); 2],
+        );
+
+        assert!(instance.is_symmetric());
+        assert!(!instance.satisfies_triangle_inequality());
+        assert!(!asymmetric.is_symmetric());
+    }
+
+    #[test]
+    fn precedence_with_three_cycle_is_detected() {
+        let instance = create_four_city_instance().with_precedences(vec![(1, 2), (2, 3), (3, 1)]);
+
+        assert!(instance.precedence_has_cycle());
+    }
+
+    #[test]
+    fn acyclic_precedences_have_no_cycle() {
+        let instance =
+            create_four_city_instance().with_precedences(vec![(1, 2), (2, 3), (1, 3), (0, 3)]);
+
+        assert!(!instance.precedence_has_cycle());
+    }
+
+    #[test]
+    fn window_tightness_is_small_for_tight_windows() {
+        let instance = TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 10.0, 10.0],
+                vec![10.0, 0.0, 10.0],
+                vec![10.0, 10.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (50.0, 55.0), (0.0, 500.0)],
+        );
+
+        assert_eq!(instance.average_edge_length(), 10.0);
+        assert_eq!(instance.window_tightness(), vec![100.0, 0.5, 50.0]);
+    }
+
+    #[test]
+    fn pinned_city_is_only_allowed_at_its_position() {
+        let instance = create_four_city_instance().with_pinned_positions(vec![(2, 1)]);
+
+        assert!(instance.allows_city_at(1, 2));
+        assert!(!instance.allows_city_at(1, 1));
+        assert!(!instance.allows_city_at(3, 2));
+        assert!(instance.allows_city_at(3, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn two_cities_pinned_to_same_position_panics() {
+        create_four_city_instance().with_pinned_positions(vec![(2, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn assignment_lower_bound_pairs_up_close_cities() {
+        // best assignment is the two 2-cycles 0 <-> 1 and 2 <-> 3, while the best tour costs 13
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 1.0, 5.0, 5.0],
+                vec![1.0, 0.0, 5.0, 5.0],
+                vec![5.0, 5.0, 0.0, 2.0],
+                vec![5.0, 5.0, 2.0, 0.0],
+            ],
+            vec![(0.0, 100.0); 4],
+        );
+
+        assert_eq!(instance.assignment_lower_bound(), 6.0);
+    }
+
+    #[test]
+    fn equivalent_city_classes_groups_interchangeable_cities() {
+        // 1 and 3 have the same distances to everyone and identical windows, 2 differs in its window
+        let create_instance = || {
+            TSPInstance::new(
+                4,
+                vec![
+                    vec![0.0, 4.0, 4.0, 4.0],
+                    vec![4.0, 0.0, 2.0, 7.0],
+                    vec![4.0, 2.0, 0.0, 2.0],
+                    vec![4.0, 7.0, 2.0, 0.0],
+                ],
+                vec![(0.0, 100.0), (10.0, 50.0), (0.0, 100.0), (10.0, 50.0)],
+            )
+        };
+
+        assert_eq!(
+            create_instance().equivalent_city_classes(),
+            vec![vec![1, 3]]
+        );
+        assert!(
+            create_instance()
+                .with_service_times(vec![0.0, 1.0, 0.0, 2.0])
+                .equivalent_city_classes()
+                .is_empty()
+        );
+        assert!(
+            create_instance()
+                .with_precedences(vec![(1, 2)])
+                .equivalent_city_classes()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn tsplib_format_is_parsed() {
+        let content = "NAME : test\nTYPE : TSPTW\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EUC_2D\nNODE_COORD_SECTION\n1 0 0\n2 3 4\n3 0 1.6\nTIME_WINDOW_SECTION\n1 0 100\n2 5 50\n3 10 60\nEOF\n";
+
+        let instance = TSPInstance::parse_tsplib(content).unwrap();
+
+        assert_eq!(instance.len(), 3);
+        assert_eq!(instance.dist_from_to(0, 1), 5.0);
+        assert_eq!(instance.dist_from_to(1, 0), 5.0);
+        assert_eq!(instance.dist_from_to(0, 2), 2.0);
+        assert_eq!(
+            instance.time_windows,
+            vec![(0.0, 100.0), (5.0, 50.0), (10.0, 60.0)]
+        );
+    }
+
+    #[test]
+    fn from_coordinates_computes_euclidean_distances() {
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (3.0, 4.0), (0.0, 1.5)],
+            vec![(0.0, 100.0); 3],
+        );
+
+        assert_eq!(instance.dist_from_to(0, 1), 5.0);
+        assert_eq!(instance.dist_from_to(1, 0), 5.0);
+        assert_eq!(instance.dist_from_to(0, 2), 1.5);
+        assert_eq!(instance.dist_from_to(2, 2), 0.0);
+        assert_eq!(instance.coordinates().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn metric_instance_matches_matrix_instance() {
+        let coords = vec![(0.0, 0.0), (3.0, 4.0), (0.0, 1.5)];
+        let matrix = TSPInstance::from_coordinates(coords.clone(), vec![(0.0, 100.0); 3]);
+        let metric = TSPInstance::from_metric(EuclideanMetric::new(coords), vec![(0.0, 100.0); 3]);
+
+        assert!(metric.distances.is_empty());
+        assert_eq!(metric.len(), 3);
+        for from in 0..3 {
+            for to in 0..3 {
+                assert_eq!(metric.dist_from_to(from, to), matrix.dist_from_to(from, to));
+            }
+        }
+        assert_eq!(
+            metric.assignment_lower_bound(),
+            matrix.assignment_lower_bound()
+        );
+    }
+
+    #[test]
+    fn tsplib_with_unsupported_edge_weight_type_is_rejected() {
+        let content = "DIMENSION : 1\nEDGE_WEIGHT_TYPE : GEO\nNODE_COORD_SECTION\n1 0 0\n";
+
+        assert!(matches!(
+            TSPInstance::parse_tsplib(content),
+            Err(InstanceParseError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let plain = "3\n0 1 2\n1 0 3\n2 3 0\n0 100\n5 50\n10 60\n";
+        let commented = "# three cities\n3\n\n# distances\n0 1 2\n1 0 3\n  # middle of the matrix\n2 3 0\n\n# windows\n0 100\n\n5 50\n# last one\n10 60\n";
+
+        let from_plain = TSPInstance::parse(plain, InstanceLayout::Full).unwrap();
+        let from_commented = TSPInstance::parse(commented, InstanceLayout::Full).unwrap();
+
+        assert_eq!(from_plain.distances, from_commented.distances);
+        assert_eq!(from_plain.time_windows, from_commented.time_windows);
+    }
+
+    #[test]
+    fn invalid_city_count_after_comments_reports_its_line() {
+        let content = "# three cities\n\nthree\n0 1 2\n1 0 3\n2 3 0\n0 100\n5 50\n10 60\n";
+
+        assert!(matches!(
+            TSPInstance::parse(content, InstanceLayout::Full),
+            Err(InstanceParseError::InvalidNumber { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn validate_instance_dir_reports_bad_files() {
+        let dir = std::env::temp_dir().join(format!("rsopt_validate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a_good.txt"), "2\n0 1\n1 0\n0 10\n0 10\n").unwrap();
+        std::fs::write(dir.join("b_bad_number.txt"), "2\n0 x\n1 0\n0 10\n0 10\n").unwrap();
+        std::fs::write(dir.join("c_truncated.txt"), "2\n0 1\n1 0\n0 10\n").unwrap();
+
+        let results = validate_instance_dir(dir.clone());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["a_good.txt", "b_bad_number.txt", "c_truncated.txt"]
+        );
+        assert!(results[0].1.is_ok());
+        assert!(matches!(
+            results[1].1,
+            Err(InstanceParseError::InvalidNumber { line: 2, .. })
+        ));
+        assert!(matches!(
+            results[2].1,
+            Err(InstanceParseError::MissingLines {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn truncating_distances_matches_published_value() {
+        // published with distances truncated to one decimal: every side of the square counts as 0.9
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (0.99, 0.0), (0.99, 0.99), (0.0, 0.99)],
+            vec![(0.0, 100.0); 4],
+        );
+        let published = 3.6;
+        let tour_length = |instance: &TSPInstance| {
+            (0..4)
+                .map(|i| instance.dist_from_to(i, (i + 1) % 4))
+                .sum::<f64>()
+        };
+
+        assert!((tour_length(&instance) - published).abs() > 0.1);
+
+        let instance = instance.with_distance_rounding(DistanceRounding::TruncateOneDecimal);
+        assert!((tour_length(&instance) - published).abs() < 1e-9);
+    }
+
+    #[test]
+    fn embed_2d_recovers_coordinates_up_to_rigid_motion() {
+        let coords = vec![(0.0, 0.0), (3.0, 0.0), (0.0, 4.0), (5.0, 5.0), (1.0, 2.0)];
+        let instance = TSPInstance::from_coordinates(coords.clone(), vec![(0.0, 100.0); 5]);
+
+        let embedded = instance.embed_2d();
+
+        // rotation, reflection and translation preserve all pairwise distances
+        let dist = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+            ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+        };
+        for i in 0..coords.len() {
+            for j in 0..coords.len() {
+                assert!((dist(embedded[i], embedded[j]) - dist(coords[i], coords[j])).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn infinite_distances_round_trip_and_are_forbidden() {
+        let instance = TSPInstance::new(
+            2,
+            vec![vec![0.0, f64::INFINITY], vec![5.0, 0.0]],
+            vec![(0.0, 100.0); 2],
+        )
+        .with_forbidden_threshold(4.0);
+        let path = std::env::temp_dir().join(format!("rsopt_inf_{}.txt", std::process::id()));
+
+        instance.to_file(path.clone()).unwrap();
+        let read = TSPInstance::from_file(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+
+        assert_eq!(read.dist_from_to(0, 1), f64::INFINITY);
+        assert!(read.is_forbidden(0, 1));
+        assert!(!read.is_forbidden(1, 0));
+        assert!(instance.is_forbidden(1, 0));
+    }
+
+    #[test]
+    fn to_file_round_trips_through_from_file() {
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (3.0, 0.5), (1.0, 4.0)],
+            vec![(0.0, 100.0), (2.5, 30.25), (1.0, 50.0)],
+        );
+        let path = std::env::temp_dir().join(format!("rsopt_to_file_{}.txt", std::process::id()));
+
+        instance.to_file(path.clone()).unwrap();
+        let read = TSPInstance::from_file(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+
+        assert_eq!(read.len(), instance.len());
+        for from in 0..instance.len() {
+            for to in 0..instance.len() {
+                assert!(
+                    (read.dist_from_to(from, to) - instance.dist_from_to(from, to)).abs() < 1e-6
+                );
+            }
+            assert_eq!(read.window_of(from), instance.window_of(from));
+        }
+    }
+
+    #[test]
+    fn savings_matrix_is_sorted_by_decreasing_savings() {
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 4.0, 5.0, 3.0],
+                vec![4.0, 0.0, 2.0, 6.0],
+                vec![5.0, 2.0, 0.0, 7.0],
+                vec![3.0, 6.0, 7.0, 0.0],
+            ],
+            vec![(0.0, 100.0); 4],
+        );
+
+        assert_eq!(
+            instance.savings_matrix(),
+            vec![(1, 2, 7.0), (1, 3, 1.0), (2, 3, 1.0)]
+        );
+    }
+
+    #[test]
+    fn infeasibility_witnesses_identifies_unreachable_window() {
+        // city 2 is 5 away from the depot but closes at 3
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (5.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 3.0), (0.0, 100.0)],
+        );
+        assert_eq!(instance.infeasibility_witnesses(), vec![2]);
+
+        let relaxed = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (5.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 5.0), (0.0, 100.0)],
+        );
+        assert!(relaxed.infeasibility_witnesses().is_empty());
+    }
+
+    #[test]
+    fn feasibility_precheck_reports_unreachable_city() {
+        // city 2 is 5 away from the depot but closes at 3
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (5.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 3.0), (0.0, 100.0)],
+        );
+
+        assert_eq!(
+            instance.feasibility_precheck(),
+            Err(InfeasibilityReason::UnreachableCity { city: 2 })
+        );
+        assert!(
+            instance
+                .with_optional_cities(vec![false, false, true, false])
+                .feasibility_precheck()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn feasibility_precheck_reports_conflicting_windows() {
+        // 1 has to be reached by time 1 and 2 by time 2, but they are 2 apart
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        );
+
+        assert_eq!(
+            instance.feasibility_precheck(),
+            Err(InfeasibilityReason::ConflictingWindows {
+                first: 1,
+                second: 2
+            })
+        );
+    }
+
+    #[test]
+    fn feasibility_precheck_ignores_return_of_open_tour() {
+        // the depot closes before anything else can be served
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 1.0), (0.0, 100.0), (0.0, 100.0)],
+        );
+
+        assert_eq!(
+            instance.feasibility_precheck(),
+            Err(InfeasibilityReason::NoReturn { city: 1 })
+        );
+        assert!(instance.with_open_tour(true).feasibility_precheck().is_ok());
+    }
+
+    #[test]
+    fn feasibility_precheck_passes_solomon_instances() {
+        for name in ["rc_201.1", "rc_204.1", "rc_206.1"] {
+            let instance =
+                TSPInstance::from_file(format!("instances/SolomonPotvinBengio/{name}.txt").into())
+                    .unwrap();
+            assert!(instance.feasibility_precheck().is_ok(), "{name}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_instance() {
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 100.0), (1.0, 50.0), (0.0, 60.0), (5.0, 70.0)],
+        )
+        .with_service_times(vec![0.0, 1.0, 2.0, 3.0])
+        .with_optional_cities(vec![false, false, true, false])
+        .with_precedences(vec![(1, 3)])
+        .with_pinned_positions(vec![(2, 2)])
+        .with_depot(1)
+        .with_distance_rounding(DistanceRounding::TruncateOneDecimal)
+        .with_forbidden_threshold(10.0)
+        .with_open_tour(true);
+
+        let json = serde_json::to_string(&instance).unwrap();
+        let restored: TSPInstance = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.dist_from_to(0, 2), 1.4);
+        assert_eq!(restored.service_time_of(3), 3.0);
+        assert!(restored.is_optional(2));
+        assert_eq!(*restored.precedences(), vec![(1, 3)]);
+        assert!(!restored.allows_city_at(2, 1));
+        assert_eq!(restored.depot(), 1);
+        assert_eq!(restored.coordinates(), instance.coordinates());
+        assert!(restored.is_open_tour());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_stores_metric_instance_as_matrix() {
+        let instance = TSPInstance::from_metric(
+            EuclideanMetric::new(vec![(0.0, 0.0), (3.0, 4.0)]),
+            vec![(0.0, 100.0); 2],
+        );
+
+        let json = serde_json::to_string(&instance).unwrap();
+        let restored: TSPInstance = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.dist_from_to(0, 1), 5.0);
+        assert!(restored.metric.is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_inconsistent_instance() {
+        let mut json: serde_json::Value =
+            serde_json::to_value(create_four_city_instance()).unwrap();
+        json["depot"] = 4.into();
+
+        assert!(serde_json::from_value::<TSPInstance>(json).is_err());
+
+        let mut json: serde_json::Value =
+            serde_json::to_value(create_four_city_instance()).unwrap();
+        json["pinned_positions"] = serde_json::json!([[1, 4]]);
+
+        assert!(serde_json::from_value::<TSPInstance>(json).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_infinite_distances() {
+        let instance = TSPInstance::new(
+            2,
+            vec![vec![0.0, f64::INFINITY], vec![5.0, 0.0]],
+            vec![(0.0, 100.0); 2],
+        );
+
+        let json = serde_json::to_string(&instance).unwrap();
+        let restored: TSPInstance = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.dist_from_to(0, 1), f64::INFINITY);
+        assert!(restored.is_forbidden(0, 1));
+        assert_eq!(restored.dist_from_to(1, 0), 5.0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{TSPInstance, TSPSolution};
+
+// Reads the TOUR_SECTION of a TSPLIB .opt.tour file. Cities are 1-based in the file, the section ends with -1 or EOF.
+// The tour is rotated to start at the depot of the instance and, unless the instance has open tours, closed by
+// returning to it.
+pub fn read_opt_tour(path: PathBuf, instance: Arc<TSPInstance>) -> Result<TSPSolution, String> {
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read file {}: {e}", path.display()))?;
+
+    parse_opt_tour(&content, instance)
+}
+
+fn parse_opt_tour(content: &str, instance: Arc<TSPInstance>) -> Result<TSPSolution, String> {
+    let mut lines = content.lines().map(str::trim);
+
+    if !lines.any(|line| line == "TOUR_SECTION") {
+        return Err("Missing TOUR_SECTION".to_string());
+    }
+
+    let mut tour: Vec<usize> = Vec::with_capacity(instance.len() + 1);
+    for token in lines.flat_map(str::split_whitespace) {
+        if token == "-1" || token == "EOF" {
+            break;
+        }
+        let id: usize = token
+            .parse()
+            .map_err(|_| format!("Could not convert {token} to a city id"))?;
+        if id == 0 || id > instance.len() {
+            return Err(format!(
+                "City id {id} is out of range for {} cities",
+                instance.len()
+            ));
+        }
+        tour.push(id - 1);
+    }
+
+    if tour.len() != instance.len() {
+        return Err(format!(
+            "Tour has {} cities, but instance has {}",
+            tour.len(),
+            instance.len()
+        ));
+    }
+
+    let depot = instance.depot();
+    let depot_position = tour
+        .iter()
+        .position(|&city| city == depot)
+        .ok_or_else(|| "Tour does not contain the depot".to_string())?;
+    tour.rotate_left(depot_position);
+    if instance.len() > 1 && !instance.is_open_tour() {
+        tour.push(depot);
+    }
+
+    Ok(TSPSolution::new(instance, tour))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_instance() -> TSPInstance {
+        TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1000.0, 1.0],
+                vec![1000.0, 0.0, 1000.0],
+                vec![1000.0, 100.0, 0.0],
+            ],
+            vec![(0.0, 1200.0), (200.0, 2000.0), (5.0, 2000.0)],
+        )
+    }
+
+    #[test]
+    fn parses_opt_tour_into_valid_solution() {
+        let content =
+            "NAME : test.opt.tour\nTYPE : TOUR\nDIMENSION : 3\nTOUR_SECTION\n2\n1\n3\n-1\nEOF\n";
+
+        let sol = parse_opt_tour(content, Arc::new(create_test_instance())).unwrap();
+
+        assert_eq!(*sol.get_path(), vec![0, 2, 1, 0]);
+        assert!(sol.is_valid());
+    }
+
+    #[test]
+    fn rotates_opt_tour_to_start_at_configured_depot() {
+        let content = "TOUR_SECTION\n1\n3\n2\n-1\n";
+        let instance = Arc::new(create_test_instance().with_depot(1));
+
+        let sol = parse_opt_tour(content, instance).unwrap();
+
+        assert_eq!(*sol.get_path(), vec![1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn does_not_close_opt_tour_of_open_instance() {
+        let content = "TOUR_SECTION\n2\n1\n3\n-1\n";
+        let instance = Arc::new(create_test_instance().with_open_tour(true));
+
+        let sol = parse_opt_tour(content, instance).unwrap();
+
+        assert_eq!(*sol.get_path(), vec![0, 2, 1]);
+        assert!(sol.is_valid());
+    }
+
+    #[test]
+    fn rejects_tour_with_wrong_number_of_cities() {
+        let content = "TOUR_SECTION\n1\n3\n-1\n";
+
+        assert!(parse_opt_tour(content, Arc::new(create_test_instance())).is_err());
+    }
+}
@@ -1,13 +1,14 @@
 use std::collections::HashSet;
 use std::fmt::Display;
 use std::iter::zip;
-use std::ops::Add;
+use std::ops::{Add, Mul, Sub};
 use std::sync::Arc;
-use log::warn;
 
 use super::TSPInstance;
+use super::tsp_utility::{INITIAL_HASH_64, calc_commutative_hash_64};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeDist {
     pub time: f64,
     pub dist: f64,
@@ -24,6 +25,145 @@ impl Add for TimeDist {
     }
 }
 
+impl Sub for TimeDist {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        TimeDist {
+            time: self.time - rhs.time,
+            dist: self.dist - rhs.dist,
+        }
+    }
+}
+
+impl Mul<f64> for TimeDist {
+    type Output = Self;
+
+    fn mul(self, factor: f64) -> Self::Output {
+        TimeDist {
+            time: self.time * factor,
+            dist: self.dist * factor,
+        }
+    }
+}
+
+/// One stop of a tour. The arrival is the time service starts, i.e. after waiting for the window to open.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StopRecord {
+    pub city: usize,
+    pub arrival: f64,
+    pub departure: f64,
+    pub wait: f64,
+    pub window: (f64, f64),
+}
+
+/// A stop of the path that is reached after its window closed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WindowViolation {
+    /// Index of the stop in the path.
+    pub step: usize,
+    pub city: usize,
+    pub window: (f64, f64),
+    pub lateness: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RankedViolation {
+    pub violation: WindowViolation,
+    /// Lateness relative to the width of the window, infinite for zero-width windows.
+    pub severity: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValidationError {
+    /// The city at this index of the path was already visited before.
+    RevisitedCity {
+        city: usize,
+        step: usize,
+    },
+    WindowMissed {
+        city: usize,
+        arrival: f64,
+        window_end: f64,
+    },
+    NotRoundTrip,
+    /// The city is visited before a city that is required to precede it.
+    PrecedenceViolated {
+        before: usize,
+        after: usize,
+    },
+    /// The path is too short, too long or leaves out a mandatory city.
+    WrongLength,
+    /// An open tour does not start at the depot.
+    WrongStart,
+    /// The path travels along an edge the instance forbids.
+    ForbiddenEdge {
+        from: usize,
+        to: usize,
+    },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::RevisitedCity { city, step } => {
+                write!(f, "city {city} at step {step} was already visited")
+            }
+            ValidationError::WindowMissed {
+                city,
+                arrival,
+                window_end,
+            } => write!(
+                f,
+                "city {city} is reached at {arrival}, after its window closed at {window_end}"
+            ),
+            ValidationError::NotRoundTrip => write!(f, "the path does not return to its start"),
+            ValidationError::PrecedenceViolated { before, after } => {
+                write!(f, "city {after} is visited before city {before}")
+            }
+            ValidationError::WrongLength => {
+                write!(f, "the path does not visit every mandatory city once")
+            }
+            ValidationError::WrongStart => write!(f, "the open path does not start at the depot"),
+            ValidationError::ForbiddenEdge { from, to } => {
+                write!(f, "the edge from city {from} to city {to} is forbidden")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Serializable form of a `TSPSolution`, which refers to its instance and thus cannot be serialized itself.
+/// Holds the path plus a summary of it; `into_solution` reattaches it to its instance.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SolutionDto {
+    pub path: Vec<usize>,
+    pub time_distance: TimeDist,
+    pub total_wait_time: f64,
+    pub is_valid: bool,
+}
+
+#[cfg(feature = "serde")]
+impl SolutionDto {
+    pub fn into_solution(self, instance: Arc<TSPInstance>) -> TSPSolution {
+        TSPSolution::new(instance, self.path)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<&TSPSolution> for SolutionDto {
+    fn from(solution: &TSPSolution) -> Self {
+        SolutionDto {
+            path: solution.path.clone(),
+            time_distance: solution.get_time_distance(),
+            total_wait_time: solution.total_wait_time(),
+            is_valid: solution.is_valid(),
+        }
+    }
+}
+
 pub struct TSPSolution {
     instance: Arc<TSPInstance>,
     path: Vec<usize>,
@@ -80,7 +220,8 @@ impl TSPSolution {
             let start = pairs[0];
             let end = pairs[1];
             let next_distance = self.instance.dist_from_to(start, end);
-            let time_diff = (time + next_distance).max(self.instance.window_of(end).0) - time;
+            let departure = time + self.instance.service_time_of(start);
+            let time_diff = (departure + next_distance).max(self.instance.window_of(end).0) - time;
             time += time_diff;
             out.push(TimeDist {
                 time: time_diff,
@@ -90,25 +231,293 @@ impl TSPSolution {
         out
     }
 
+    /// The path of the round trip rotated to start and end at the depot. On symmetric instances, a tour and its
+    /// reversal have the same length, so the direction is fixed as well: the neighbor of the depot with the
+    /// smaller index comes first. Paths that are no round trip are returned unchanged.
+    pub fn canonical_path(&self) -> Vec<usize> {
+        let depot = self.instance.depot();
+        let is_round_trip = self.path.len() > 1 && self.path.first() == self.path.last();
+        let depot_index = self.path.iter().position(|&city| city == depot);
+        let (true, Some(depot_index)) = (is_round_trip, depot_index) else {
+            return self.path.clone();
+        };
+
+        let cycle = &self.path[..self.path.len() - 1];
+        let mut canonical: Vec<usize> = cycle[depot_index..]
+            .iter()
+            .chain(&cycle[..depot_index])
+            .copied()
+            .collect();
+        let last = canonical.len() - 1;
+        if self.instance.is_symmetric() && last > 1 && canonical[1] > canonical[last] {
+            canonical[1..].reverse();
+        }
+        canonical.push(depot);
+        canonical
+    }
+
+    /// Order-independent hash of the visited cities, as used by the solver to bucket similar nodes.
+    pub fn visited_hash(&self) -> u64 {
+        self.path
+            .iter()
+            .skip(1)
+            .fold(INITIAL_HASH_64, |hash, &city| {
+                calc_commutative_hash_64(hash, city)
+            })
+    }
+
+    /// Machine-readable form of the solution with the totals and the time and distance of every edge.
+    pub fn to_json(&self) -> String {
+        let path: Vec<String> = self.path.iter().map(usize::to_string).collect();
+        let edges: Vec<String> = zip(self.get_time_distance_diffs(), self.path.windows(2))
+            .map(|(time_dist, pair)| {
+                format!(
+                    r#"{{"from":{},"to":{},"time":{},"dist":{}}}"#,
+                    pair[0], pair[1], time_dist.time, time_dist.dist
+                )
+            })
+            .collect();
+        let total = self.get_time_distance();
+
+        format!(
+            r#"{{"num_cities":{},"path":[{}],"distance":{},"time":{},"edges":[{}]}}"#,
+            self.instance.len(),
+            path.join(","),
+            total.dist,
+            total.time,
+            edges.join(",")
+        )
+    }
+
+    /// The schedule as CSV with a header line and one row per stop, the depot being the first and last row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,city,arrival,departure,wait,window_start,window_end\n");
+        for (step, stop) in self.schedule().iter().enumerate() {
+            csv.push_str(&format!(
+                "{step},{},{:.2},{:.2},{:.2},{:.2},{:.2}\n",
+                stop.city, stop.arrival, stop.departure, stop.wait, stop.window.0, stop.window.1
+            ));
+        }
+        csv
+    }
+
+    pub fn edge_costs(&self) -> Vec<(usize, usize, f64)> {
+        self.path
+            .windows(2)
+            .map(|pair| {
+                let (from, to) = (pair[0], pair[1]);
+                (from, to, self.instance.dist_from_to(from, to))
+            })
+            .collect()
+    }
+
     pub fn get_time_distance(&self) -> TimeDist {
         self.get_time_distance_diffs().iter().fold(
             TimeDist {
                 time: self.instance.window_of(self.path[0]).0,
                 dist: 0.0,
             },
-            |x, y| x + y.clone(),
+            |x, y| x + *y,
         )
     }
 
+    /// The route sheet of the tour: when each stop is reached, left and how long was waited there.
+    pub fn schedule(&self) -> Vec<StopRecord> {
+        let Some(&first) = self.path.first() else {
+            return Vec::new();
+        };
+
+        let start = self.instance.window_of(first).0;
+        let mut out = vec![StopRecord {
+            city: first,
+            arrival: start,
+            departure: start + self.instance.service_time_of(first),
+            wait: 0.0,
+            window: self.instance.window_of(first),
+        }];
+        for pair in self.path.windows(2) {
+            let departure = out.last().unwrap().departure;
+            let reached = departure + self.instance.dist_from_to(pair[0], pair[1]);
+            let window = self.instance.window_of(pair[1]);
+            let arrival = reached.max(window.0);
+            out.push(StopRecord {
+                city: pair[1],
+                arrival,
+                departure: arrival + self.instance.service_time_of(pair[1]),
+                wait: arrival - reached,
+                window,
+            });
+        }
+        out
+    }
+
+    /// Time at which each stop of the path is served, after waiting for its window to open.
+    pub fn arrival_times(&self) -> Vec<f64> {
+        self.schedule().iter().map(|stop| stop.arrival).collect()
+    }
+
+    /// Time at which each stop of the path is left, i.e. its arrival time plus its service time.
+    pub fn departure_times(&self) -> Vec<f64> {
+        self.schedule().iter().map(|stop| stop.departure).collect()
+    }
+
+    /// For every city of the path, how much later it could have been reached without missing its window.
+    /// Near-zero slacks mark the binding windows, negative ones the missed windows.
+    pub fn slacks(&self) -> Vec<f64> {
+        self.schedule()
+            .iter()
+            .map(|stop| stop.window.1 - (stop.arrival - stop.wait))
+            .collect()
+    }
+
+    /// Total time spent idle waiting for windows to open, not counting service times.
+    pub fn total_wait_time(&self) -> f64 {
+        self.schedule().iter().map(|stop| stop.wait).sum()
+    }
+
+    pub fn total_slack(&self) -> f64 {
+        self.slacks().iter().sum()
+    }
+
+    /// All stops reached after their window closed, in path order. The tour continues from a late stop
+    /// as if it had been served on arrival.
+    pub fn window_violations(&self) -> Vec<WindowViolation> {
+        zip(&self.path, self.slacks())
+            .enumerate()
+            .filter(|(_, (_, slack))| *slack < 0.0)
+            .map(|(step, (&city, slack))| WindowViolation {
+                step,
+                city,
+                window: self.instance.window_of(city),
+                lateness: -slack,
+            })
+            .collect()
+    }
+
+    /// The window violations, worst first.
+    pub fn ranked_violations(&self) -> Vec<RankedViolation> {
+        let mut ranked: Vec<RankedViolation> = self
+            .window_violations()
+            .into_iter()
+            .map(|violation| {
+                let width = violation.window.1 - violation.window.0;
+                let severity = if width > 0.0 {
+                    violation.lateness / width
+                } else {
+                    f64::INFINITY
+                };
+                RankedViolation {
+                    violation,
+                    severity,
+                }
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.severity.total_cmp(&a.severity));
+        ranked
+    }
+
+    /// Largest uniform relative increase of all travel times the tour absorbs without violating a time window,
+    /// e.g. 0.5 if all distances may grow by 50 %. Returns 0 if the tour is already infeasible.
+    pub fn travel_time_robustness(&self) -> f64 {
+        const MAX_SCALE: f64 = 1e6;
+
+        if !self.meets_windows_with_travel_scale(1.0) {
+            return 0.0;
+        }
+        if self.meets_windows_with_travel_scale(MAX_SCALE) {
+            return f64::INFINITY;
+        }
+
+        let mut feasible = 1.0;
+        let mut infeasible = MAX_SCALE;
+        for _ in 0..100 {
+            let mid = (feasible + infeasible) / 2.0;
+            if self.meets_windows_with_travel_scale(mid) {
+                feasible = mid;
+            } else {
+                infeasible = mid;
+            }
+        }
+        feasible - 1.0
+    }
+
+    fn meets_windows_with_travel_scale(&self, scale: f64) -> bool {
+        let Some(&first) = self.path.first() else {
+            return true;
+        };
+
+        let mut time = f64::max(0.0, self.instance.window_of(first).0);
+        for pair in self.path.windows(2) {
+            time += self.instance.service_time_of(pair[0])
+                + self.instance.dist_from_to(pair[0], pair[1]) * scale;
+            let (start_time, end_time) = self.instance.window_of(pair[1]);
+            if time > end_time {
+                return false;
+            }
+            time = time.max(start_time);
+        }
+        true
+    }
+
     pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Checks that the path is a complete, window-feasible roundtrip and reports the first violation otherwise.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_complete()?;
+        self.validate_subsolution()
+    }
+
+    fn validate_complete(&self) -> Result<(), ValidationError> {
         // if we have only one city, this is already a roundtrip, otherwise we need one more step to get back to the deposit.
-        self.path.len() == &self.instance.len() + (if self.instance.len() == 1 { 0 } else { 1 })
-            && self.is_valid_subsolution()
+        let min_len = if self.instance.len() == 1 { 1 } else { 2 };
+        if self.path.len() < min_len
+            || (self.instance.len() == 1 && self.path.len() > 1)
+            || self
+                .skipped_cities()
+                .iter()
+                .any(|&city| !self.instance.is_optional(city))
+        {
+            return Err(ValidationError::WrongLength);
+        }
+        if self.instance.is_open_tour() {
+            if self.path.first() != Some(&self.instance.depot()) {
+                return Err(ValidationError::WrongStart);
+            }
+        } else if self.path.first() != self.path.last() {
+            return Err(ValidationError::NotRoundTrip);
+        }
+        Ok(())
+    }
+
+    /// Whether the path is a roundtrip visiting every mandatory city, regardless of the time windows.
+    pub fn is_complete(&self) -> bool {
+        self.validate_complete().is_ok()
+    }
+
+    /// Sum over all stops of how late they were reached after their window closed.
+    pub fn total_lateness(&self) -> f64 {
+        self.slacks().iter().map(|slack| (-slack).max(0.0)).sum()
+    }
+
+    /// Cities of the instance which the path does not visit.
+    pub fn skipped_cities(&self) -> Vec<usize> {
+        (0..self.instance.len())
+            .filter(|city| !self.path.contains(city))
+            .collect()
     }
 
     pub fn is_valid_subsolution(&self) -> bool {
+        self.validate_subsolution().is_ok()
+    }
+
+    /// Checks that no city is visited twice, except for returning to the start, that no forbidden edge is used and
+    /// that all windows are met.
+    pub fn validate_subsolution(&self) -> Result<(), ValidationError> {
         if self.path.is_empty() {
-            return true;
+            return Ok(());
         }
 
         let mut last_visited = self.path[0];
@@ -117,20 +526,40 @@ impl TSPSolution {
         visited.insert(last_visited);
 
         for (cnt, &node) in self.path[1..self.path.len()].iter().enumerate() {
-            if !visited.insert(node) && cnt < self.path.len() - 2 {
-                warn!(
-                    "Invalid subsolution because node {} was already visited",
-                    node
-                );
-                return false;
+            let may_return = cnt == self.path.len() - 2 && !self.instance.is_open_tour();
+            if !visited.insert(node) && !may_return {
+                return Err(ValidationError::RevisitedCity {
+                    city: node,
+                    step: cnt + 1,
+                });
+            }
+
+            if let Some(&(before, after)) = self
+                .instance
+                .precedences()
+                .iter()
+                .find(|&&(before, after)| after == node && !visited.contains(&before))
+            {
+                return Err(ValidationError::PrecedenceViolated { before, after });
             }
 
-            time += self.instance.dist_from_to(last_visited, node);
+            if self.instance.is_forbidden(last_visited, node) {
+                return Err(ValidationError::ForbiddenEdge {
+                    from: last_visited,
+                    to: node,
+                });
+            }
+
+            time += self.instance.service_time_of(last_visited)
+                + self.instance.dist_from_to(last_visited, node);
 
             let (start_time, end_time) = self.instance.window_of(node);
             if time > end_time {
-                warn!("Invalid subsolution in node {cnt} (which is city {node}) because end time {end_time} of time window does not contain {time}");
-                return false;
+                return Err(ValidationError::WindowMissed {
+                    city: node,
+                    arrival: time,
+                    window_end: end_time,
+                });
             }
 
             time = time.max(start_time); // if we arrive too early we have to wait.
@@ -138,13 +567,13 @@ impl TSPSolution {
             last_visited = node;
         }
         if let Some(last) = self.path.last()
+            && !self.instance.is_open_tour()
             && self.path.len() == self.get_instance().len() + 1
             && *last != self.path[0]
         {
-            warn!("Invalid subsolution because last node is not start node");
-            return false;
+            return Err(ValidationError::NotRoundTrip);
         }
-        true
+        Ok(())
     }
 }
 
@@ -257,9 +686,90 @@ mod tests {
                 dist: 0.0
             }
         );
+        assert_eq!(sol.arrival_times(), vec![0.0, 2000.0, 2000.0]);
+        assert_eq!(sol.departure_times(), sol.arrival_times());
+        assert_eq!(sol.total_wait_time(), 2000.0);
         assert!(sol.is_valid())
     }
 
+    #[test]
+    fn test_open_tour_must_not_return() {
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)],
+                vec![(0.0, 1000.0); 3],
+            )
+            .with_open_tour(true),
+        );
+        let validate = |path| TSPSolution::new(instance.clone(), path).validate();
+
+        assert_eq!(validate(vec![0, 1, 2]), Ok(()));
+        assert_eq!(
+            validate(vec![0, 1, 2, 0]),
+            Err(ValidationError::RevisitedCity { city: 0, step: 3 })
+        );
+        assert_eq!(validate(vec![1, 0, 2]), Err(ValidationError::WrongStart));
+        assert_eq!(validate(vec![0, 1]), Err(ValidationError::WrongLength));
+    }
+
+    #[test]
+    fn test_canonical_path_ignores_rotation_and_direction_on_symmetric_instance() {
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0); 4],
+        ));
+        let canonical =
+            |path: Vec<usize>| TSPSolution::new(instance.clone(), path).canonical_path();
+
+        assert_eq!(canonical(vec![0, 1, 2, 3, 0]), vec![0, 1, 2, 3, 0]);
+        assert_eq!(canonical(vec![0, 3, 2, 1, 0]), vec![0, 1, 2, 3, 0]);
+        assert_eq!(canonical(vec![2, 3, 0, 1, 2]), vec![0, 1, 2, 3, 0]);
+        assert_ne!(canonical(vec![0, 2, 1, 3, 0]), vec![0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_canonical_path_keeps_direction_on_asymmetric_instance() {
+        let sol = TSPSolution::new(create_test_instance(), vec![0, 2, 1, 0]);
+
+        assert_eq!(sol.canonical_path(), vec![0, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_time_dist_arithmetic() {
+        let a = TimeDist {
+            time: 5.0,
+            dist: 3.0,
+        };
+        let b = TimeDist {
+            time: 2.0,
+            dist: 1.0,
+        };
+
+        assert_eq!(
+            a + b,
+            TimeDist {
+                time: 7.0,
+                dist: 4.0
+            }
+        );
+        assert_eq!(
+            a - b,
+            TimeDist {
+                time: 3.0,
+                dist: 2.0
+            }
+        );
+        assert_eq!((a - b) + b, a);
+        assert_eq!(
+            a * 2.0,
+            TimeDist {
+                time: 10.0,
+                dist: 6.0
+            }
+        );
+        assert_eq!(a + TimeDist::default(), a);
+    }
+
     #[test]
     fn test_time_distance_diffs() {
         let instance = create_test_instance();
@@ -280,4 +790,250 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_service_time_makes_later_window_unreachable() {
+        let create_instance = || {
+            TSPInstance::new(
+                3,
+                vec![
+                    vec![0.0, 1.0, 1.0],
+                    vec![1.0, 0.0, 1.0],
+                    vec![1.0, 1.0, 0.0],
+                ],
+                vec![(0.0, 100.0), (0.0, 10.0), (0.0, 3.0)],
+            )
+        };
+        let path = vec![0, 1, 2, 0];
+
+        let without_service = TSPSolution::new(Arc::new(create_instance()), path.clone());
+        assert!(without_service.is_valid());
+
+        let instance = create_instance().with_service_times(vec![0.0, 5.0, 0.0]);
+        let with_service = TSPSolution::new(Arc::new(instance), path);
+        assert_eq!(with_service.arrival_times(), vec![0.0, 1.0, 7.0, 8.0]);
+        assert_eq!(with_service.departure_times(), vec![0.0, 6.0, 7.0, 8.0]);
+        assert_eq!(with_service.total_wait_time(), 0.0);
+        assert!(!with_service.is_valid());
+    }
+
+    #[test]
+    fn test_edge_costs() {
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1000.0, 1.0],
+                vec![1000.0, 0.0, 1000.0],
+                vec![1000.0, 100.0, 0.0],
+            ],
+            vec![(0.0, 1200.0), (200.0, 2000.0), (5.0, 2000.0)],
+        ));
+        let sol = TSPSolution::new(instance, vec![0, 2, 1, 0]);
+
+        assert_eq!(
+            sol.edge_costs(),
+            vec![(0, 2, 1.0), (2, 1, 100.0), (1, 0, 1000.0)]
+        );
+    }
+
+    #[test]
+    fn test_travel_time_robustness() {
+        // arriving at 1 at time 10 with window end 15 allows 50 % longer travel times
+        let instance = Arc::new(TSPInstance::new(
+            2,
+            vec![vec![0.0, 10.0], vec![10.0, 0.0]],
+            vec![(0.0, 100.0), (0.0, 15.0)],
+        ));
+        let sol = TSPSolution::new(instance, vec![0, 1, 0]);
+
+        assert!((sol.travel_time_robustness() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_total_slack_sums_slacks() {
+        let sol = TSPSolution::new(create_test_instance(), vec![0, 1, 0]);
+
+        assert_eq!(sol.slacks(), vec![101.0, 1.0, 97.0]);
+        assert_eq!(sol.total_slack(), 199.0);
+    }
+
+    #[test]
+    fn test_slack_is_zero_when_arriving_at_window_end() {
+        let instance = Arc::new(TSPInstance::new(
+            2,
+            vec![vec![0.0, 10.0], vec![10.0, 0.0]],
+            vec![(0.0, 100.0), (5.0, 10.0)],
+        ));
+        let sol = TSPSolution::new(instance, vec![0, 1, 0]);
+
+        assert_eq!(sol.slacks(), vec![100.0, 0.0, 80.0]);
+        assert!(sol.is_valid());
+    }
+
+    #[test]
+    fn test_schedule_matches_arrival_times_and_windows() {
+        let instance = create_test_instance();
+        let sol = TSPSolution::new(instance.clone(), vec![0, 1, 0]);
+
+        let schedule = sol.schedule();
+        let arrivals: Vec<f64> = schedule.iter().map(|stop| stop.arrival).collect();
+
+        assert_eq!(arrivals, sol.arrival_times());
+        assert_eq!(arrivals, vec![0.0, 2.0, 4.0]);
+        for stop in &schedule {
+            assert_eq!(stop.window, instance.window_of(stop.city));
+        }
+        assert_eq!(schedule[1].wait, 1.0);
+        assert_eq!(schedule[1].departure, 2.0);
+    }
+
+    #[test]
+    fn test_to_json_contains_path_and_edges() {
+        let sol = TSPSolution::new(create_test_instance(), vec![0, 1, 0]);
+
+        let json = sol.to_json();
+
+        let path_start = json.find(r#""path":["#).unwrap() + r#""path":["#.len();
+        let path_end = path_start + json[path_start..].find(']').unwrap();
+        let path: Vec<usize> = json[path_start..path_end]
+            .split(',')
+            .map(|city| city.parse().unwrap())
+            .collect();
+        assert_eq!(path, *sol.get_path());
+        assert_eq!(
+            json,
+            r#"{"num_cities":2,"path":[0,1,0],"distance":3,"time":4,"edges":[{"from":0,"to":1,"time":2,"dist":1},{"from":1,"to":0,"time":2,"dist":2}]}"#
+        );
+    }
+
+    #[test]
+    fn test_to_csv_has_one_row_per_stop() {
+        let sol = TSPSolution::new(create_test_instance(), vec![0, 1, 0]);
+
+        let csv = sol.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), sol.get_path().len() + 1);
+        assert_eq!(
+            lines[0],
+            "step,city,arrival,departure,wait,window_start,window_end"
+        );
+        assert_eq!(lines[2], "1,1,2.00,2.00,1.00,2.00,2.00");
+        assert!(lines[3].starts_with("2,0,"));
+    }
+
+    #[test]
+    fn test_ranked_violations_puts_narrow_window_first() {
+        // city 1 is 5 late on a window of width 100, city 2 is 2 late on a window of width 4
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 10.0, 1.0],
+                vec![1.0, 0.0, 1.0],
+                vec![1.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 100.0), (-95.0, 5.0), (5.0, 9.0)],
+        ));
+        let sol = TSPSolution::new(instance, vec![0, 1, 2, 0]);
+
+        let ranked = sol.ranked_violations();
+
+        let cities: Vec<usize> = ranked.iter().map(|r| r.violation.city).collect();
+        assert_eq!(cities, vec![2, 1]);
+        assert_eq!(ranked[0].violation.lateness, 2.0);
+        assert_eq!(ranked[0].severity, 0.5);
+        assert_eq!(ranked[1].violation.lateness, 5.0);
+        assert_eq!(ranked[1].severity, 0.05);
+    }
+
+    #[test]
+    fn test_validate_reports_reason() {
+        let validate = |path| TSPSolution::new(create_test_instance(), path).validate();
+
+        assert_eq!(validate(vec![0, 1, 0]), Ok(()));
+        assert_eq!(validate(vec![0]), Err(ValidationError::WrongLength));
+        assert_eq!(validate(vec![0, 1]), Err(ValidationError::NotRoundTrip));
+        assert_eq!(
+            validate(vec![0, 1, 1, 0]),
+            Err(ValidationError::RevisitedCity { city: 1, step: 2 })
+        );
+        assert_eq!(
+            validate(vec![1, 0, 1]),
+            Err(ValidationError::WindowMissed {
+                city: 1,
+                arrival: 5.0,
+                window_end: 2.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_precedence_violation_is_not_valid_subsolution() {
+        let instance = Arc::new(
+            TSPInstance::new(
+                3,
+                vec![
+                    vec![0.0, 1.0, 1.0],
+                    vec![1.0, 0.0, 1.0],
+                    vec![1.0, 1.0, 0.0],
+                ],
+                vec![(0.0, 100.0); 3],
+            )
+            .with_precedences(vec![(1, 2)]),
+        );
+
+        let violating = TSPSolution::new(instance.clone(), vec![0, 2]);
+
+        assert!(!violating.is_valid_subsolution());
+        assert_eq!(
+            violating.validate_subsolution(),
+            Err(ValidationError::PrecedenceViolated {
+                before: 1,
+                after: 2
+            })
+        );
+        assert!(TSPSolution::new(instance, vec![0, 1, 2, 0]).is_valid());
+    }
+
+    #[test]
+    fn test_forbidden_edge_is_not_valid() {
+        let instance = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+                vec![(0.0, 100.0); 4],
+            )
+            .with_forbidden_threshold(1.2),
+        );
+
+        let crossing = TSPSolution::new(instance.clone(), vec![0, 2, 1, 3, 0]);
+
+        assert_eq!(
+            crossing.validate(),
+            Err(ValidationError::ForbiddenEdge { from: 0, to: 2 })
+        );
+        assert!(TSPSolution::new(instance, vec![0, 1, 2, 3, 0]).is_valid());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn solution_dto_round_trip() {
+        let instance = create_test_instance();
+        let sol = TSPSolution::new(instance.clone(), vec![0, 1, 0]);
+
+        let json = serde_json::to_string(&SolutionDto::from(&sol)).unwrap();
+        let dto: SolutionDto = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(dto, SolutionDto::from(&sol));
+        assert_eq!(
+            dto.time_distance,
+            TimeDist {
+                time: 4.0,
+                dist: 3.0
+            }
+        );
+        assert!(dto.is_valid);
+        let restored = dto.into_solution(instance);
+        assert_eq!(restored.get_path(), sol.get_path());
+        assert_eq!(restored.get_time_distance(), sol.get_time_distance());
+    }
 }
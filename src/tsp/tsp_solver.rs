@@ -1,21 +1,150 @@
-use log::{debug, info};
+use log::{debug, info, trace, warn};
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
-use super::super::beamsearch::beamsearch_solver::{BeamsearchNode, BeamsearchSolver, Node, Params};
-use super::tsp_instance::TSPInstance;
+use super::super::beamsearch::beamsearch_solver::{
+    BeamsearchNode, BeamsearchSolver, CheckpointTrigger, Node, Objective, Params, SimilarityMode,
+    SolverResult,
+};
+use super::super::beamsearch::observer::{IterationEvent, NoObserver, SolverObserver};
+use super::tsp_instance::{InfeasibilityReason, TSPInstance};
 use super::tsp_solution::TSPSolution;
-use super::tsp_utility::calc_commutative_hash;
+use super::tsp_utility::{INITIAL_HASH_64, calc_commutative_hash_64};
 
-struct TSPNode {
+pub struct TSPNode {
     pub time: f64,
     pub dist: f64,
     pub target: usize,
-    pub visited_node_hash: u32,
+    pub visited_node_hash: u64,
+    /// Bit i is set if city i was visited. Only cities below 128 are tracked, see `VisitedCities`.
+    pub visited_mask: u128,
+    /// Number of stops at which the vehicle had to wait for the window to open.
+    pub wait_count: usize,
+    /// Number of optional cities left out, only known once the tour is back at the depot.
+    pub skipped: usize,
+    /// Sum of how late the cities were reached after their window closed, only nonzero for soft windows.
+    pub lateness: f64,
+    /// Lower bound on the distance still needed to complete the tour, only nonzero if enabled in the params.
+    pub lower_bound: f64,
+    /// The objective minimized by the beam search, see `ExpansionSettings::cost_of`.
+    pub cost: f64,
+}
+
+impl TSPNode {
+    fn start_at(instance: &TSPInstance, city: usize) -> Self {
+        TSPNode {
+            time: instance.window_of(city).0,
+            target: city,
+            dist: 0.0,
+            visited_node_hash: INITIAL_HASH_64,
+            visited_mask: city_bit(city),
+            wait_count: 0,
+            skipped: 0,
+            lateness: 0.0,
+            lower_bound: 0.0,
+            cost: 0.0,
+        }
+    }
+
+    fn visit(&self, instance: &TSPInstance, next_target: usize) -> Self {
+        let dist_to_next = instance.dist_from_to(self.target, next_target);
+        let reached = self.time + instance.service_time_of(self.target) + dist_to_next;
+        let (window_start, window_end) = instance.window_of(next_target);
+        let dist = self.dist + dist_to_next;
+        TSPNode {
+            time: reached.max(window_start),
+            target: next_target,
+            dist,
+            visited_node_hash: calc_commutative_hash_64(self.visited_node_hash, next_target),
+            visited_mask: self.visited_mask | city_bit(next_target),
+            wait_count: self.wait_count + usize::from(window_start > reached),
+            skipped: 0,
+            lateness: self.lateness + (reached - window_end).max(0.0),
+            lower_bound: self.lower_bound,
+            cost: dist,
+        }
+    }
+}
+
+fn city_bit(city: usize) -> u128 {
+    1u128.checked_shl(city as u32).unwrap_or(0)
+}
+
+/// The distinct cities on the path of a node. Instances with at most 128 cities use the node's bitmask,
+/// larger ones fall back to walking the ancestors.
+#[derive(Clone)]
+enum VisitedCities {
+    Mask(u128),
+    List(Vec<usize>),
+}
+
+impl VisitedCities {
+    fn of(node: &Node<TSPNode>, instance: &TSPInstance) -> Self {
+        if instance.len() <= u128::BITS as usize {
+            VisitedCities::Mask(node.data().visited_mask)
+        } else {
+            let mut cities: Vec<usize> = node.ancestors().map(|x| x.data().target).collect();
+            // a complete tour contains the depot twice
+            cities.sort_unstable();
+            cities.dedup();
+            VisitedCities::List(cities)
+        }
+    }
+
+    fn contains(&self, city: usize) -> bool {
+        match self {
+            VisitedCities::Mask(mask) => mask & city_bit(city) != 0,
+            VisitedCities::List(cities) => cities.binary_search(&city).is_ok(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            VisitedCities::Mask(mask) => mask.count_ones() as usize,
+            VisitedCities::List(cities) => cities.len(),
+        }
+    }
+
+    /// The cities among the first `nr_cities` that are not visited yet, in ascending order.
+    fn unvisited(&self, nr_cities: usize) -> Vec<usize> {
+        match self {
+            VisitedCities::Mask(mask) => {
+                let all = u128::MAX
+                    .checked_shr((u128::BITS as usize - nr_cities) as u32)
+                    .unwrap_or(0);
+                let mut remaining = all & !mask;
+                let mut cities = Vec::with_capacity(remaining.count_ones() as usize);
+                while remaining != 0 {
+                    cities.push(remaining.trailing_zeros() as usize);
+                    remaining &= remaining - 1;
+                }
+                cities
+            }
+            VisitedCities::List(_) => (0..nr_cities).filter(|i| !self.contains(*i)).collect(),
+        }
+    }
+
+    fn with(&self, city: usize) -> Self {
+        match self {
+            VisitedCities::Mask(mask) => VisitedCities::Mask(mask | city_bit(city)),
+            VisitedCities::List(cities) => {
+                let mut cities = cities.clone();
+                if let Err(position) = cities.binary_search(&city) {
+                    cities.insert(position, city);
+                }
+                VisitedCities::List(cities)
+            }
+        }
+    }
 }
 
 impl BeamsearchNode for TSPNode {
     fn fitness(&self) -> f64 {
-        self.dist
+        self.cost
     }
 
     fn level(&self) -> f64 {
@@ -24,75 +153,342 @@ impl BeamsearchNode for TSPNode {
 }
 
 fn make_tsp_solution_from_node(instance: Arc<TSPInstance>, node: &Node<TSPNode>) -> TSPSolution {
-    let mut path: Vec<usize> = node.ancestors().map(|node| node.data().target).collect();
-    path.reverse();
+    let path = node
+        .path_from_root()
+        .iter()
+        .map(|data| data.target)
+        .collect();
     TSPSolution::new(instance, path)
 }
 
-fn expand(node: &Node<TSPNode>, instance: &TSPInstance) -> Vec<TSPNode> {
-    let time = node.data().time;
-    let dist = node.data().dist;
+/// Builds the chain of nodes the solver would have created when following the path, returning its last node.
+pub fn build_node_chain(instance: &TSPInstance, path: &[usize]) -> Node<TSPNode> {
+    assert!(!path.is_empty());
 
-    let last_target = node.data().target;
-    let visited_nodes: Vec<usize> = node.ancestors().map(|x| x.data().target).collect();
+    let mut node = Node::new_root(TSPNode::start_at(instance, path[0]));
+    for &next_target in &path[1..] {
+        node = node.new_child(node.data().visit(instance, next_target));
+    }
+    node
+}
 
-    let reached_end = || {
-        visited_nodes.len()
-            == if instance.len() > 1 {
-                instance.len() + 1
-            } else {
-                1
-            }
-    };
+#[derive(Clone, Copy)]
+struct ExpansionSettings<'a> {
+    objective: Objective,
+    lookahead_depth: usize,
+    include_return_cost: bool,
+    random_candidates: Option<usize>,
+    seed: u64,
+    wait_count_weight: f64,
+    skip_penalty: f64,
+    lateness_penalty: Option<f64>,
+    /// Cheapest edge into every city, set if the completion lower bound is enabled.
+    min_incoming_edges: Option<&'a [f64]>,
+    /// Nearest neighbors of every city, closest first, set if candidate lists are enabled.
+    candidate_lists: Option<&'a [Vec<usize>]>,
+    /// If set, the depot may only be left towards this city.
+    first_city: Option<usize>,
+    /// If set, the children are shuffled, so that ties in the cost are broken differently per seed.
+    tie_break_seed: Option<u64>,
+}
 
-    if reached_end() {
+impl Default for ExpansionSettings<'_> {
+    fn default() -> Self {
+        Self {
+            objective: Objective::default(),
+            lookahead_depth: 0,
+            include_return_cost: true,
+            random_candidates: None,
+            seed: 0,
+            wait_count_weight: 0.0,
+            skip_penalty: 0.0,
+            lateness_penalty: None,
+            min_incoming_edges: None,
+            candidate_lists: None,
+            first_city: None,
+            tie_break_seed: None,
+        }
+    }
+}
+
+impl From<&Params> for ExpansionSettings<'_> {
+    fn from(params: &Params) -> Self {
+        Self {
+            objective: params.objective,
+            lookahead_depth: params.lookahead_depth,
+            include_return_cost: params.include_return_cost,
+            random_candidates: params.random_candidates,
+            seed: params.seed,
+            wait_count_weight: params.wait_count_weight,
+            skip_penalty: params.skip_penalty,
+            lateness_penalty: params.lateness_penalty,
+            min_incoming_edges: None,
+            candidate_lists: None,
+            first_city: None,
+            tie_break_seed: None,
+        }
+    }
+}
+
+impl ExpansionSettings<'_> {
+    fn cost_of(&self, node: &TSPNode) -> f64 {
+        let primary = match self.objective {
+            Objective::MinDistance => node.dist,
+            Objective::MinMakespan => node.time,
+        };
+        primary
+            + node.lower_bound
+            + self.wait_count_weight * node.wait_count as f64
+            + self.skip_penalty * node.skipped as f64
+            + self.lateness_penalty.unwrap_or(0.0) * node.lateness
+    }
+}
+
+fn expand(
+    node: &Node<TSPNode>,
+    instance: &TSPInstance,
+    settings: &ExpansionSettings,
+) -> Vec<TSPNode> {
+    expand_from(
+        node.data(),
+        &VisitedCities::of(node, instance),
+        instance,
+        settings,
+    )
+}
+
+// Optional cities can be left out, so a closed tour is complete as soon as it is back at the depot and an open
+// one as soon as every mandatory city is visited. visited includes the current target.
+fn is_complete_tour(
+    visited: &VisitedCities,
+    current_target: usize,
+    instance: &TSPInstance,
+) -> bool {
+    if instance.is_open_tour() {
+        (0..instance.len()).all(|city| instance.is_optional(city) || visited.contains(city))
+    } else if instance.len() > 1 {
+        visited.len() > 1 && current_target == instance.depot()
+    } else {
+        visited.len() == 1
+    }
+}
+
+fn expand_from(
+    current: &TSPNode,
+    visited: &VisitedCities,
+    instance: &TSPInstance,
+    settings: &ExpansionSettings,
+) -> Vec<TSPNode> {
+    let time = current.time;
+
+    let last_target = current.target;
+
+    if is_complete_tour(visited, last_target, instance) {
         return Vec::new();
     }
 
-    let remaining_nodes: Vec<_> = (0..instance.len())
-        .filter(|i| {
-            !visited_nodes.contains(i)
-                || (visited_nodes.len() == instance.len() && i == visited_nodes.last().unwrap())
-        })
+    let nr_visited = visited.len();
+    let depot = instance.depot();
+    let mut remaining_nodes = visited.unvisited(instance.len());
+    let may_return = !instance.is_open_tour()
+        && nr_visited > 1
+        && remaining_nodes.iter().all(|i| instance.is_optional(*i));
+    if may_return && let Err(position) = remaining_nodes.binary_search(&depot) {
+        remaining_nodes.insert(position, depot);
+    }
+    // optional cities whose window is missed are simply left out
+    let mandatory_nodes: Vec<_> = remaining_nodes
+        .iter()
+        .copied()
+        .filter(|i| !instance.is_optional(*i))
         .collect();
 
-    if remaining_nodes
-        .iter()
-        .any(|i| instance.window_of(*i).1 < time)
+    // with soft windows, being late is penalized in the cost instead of cutting off the node
+    let soft_windows = settings.lateness_penalty.is_some();
+
+    if !soft_windows
+        && mandatory_nodes
+            .iter()
+            .any(|i| instance.window_of(*i).1 < time)
     // if one of the nodes cannot be targeted in future because of missed window
     {
         return Vec::new();
     }
 
-    let max_possible_time = remaining_nodes
-        .iter()
-        .map(|n| instance.window_of(*n).1 as i32)
-        .min()
-        .unwrap() as f64;
+    let max_possible_time = if soft_windows {
+        f64::INFINITY
+    } else {
+        mandatory_nodes
+            .iter()
+            .map(|n| instance.window_of(*n).1 as i32)
+            .min()
+            .map_or(f64::INFINITY, f64::from)
+    };
 
     let get_next_time_for = |next_target| {
-        (node.data().time + instance.dist_from_to(last_target, next_target))
-            .max(instance.window_of(next_target).0)
+        (time
+            + instance.service_time_of(last_target)
+            + instance.dist_from_to(last_target, next_target))
+        .max(instance.window_of(next_target).0)
     };
 
-    let expanded_nodes: Vec<_> = remaining_nodes
+    let feasible_targets: Vec<_> = remaining_nodes
         .into_iter()
+        .filter(|next_target| !instance.is_forbidden(current.target, *next_target))
         .filter(|next_target| get_next_time_for(*next_target) <= max_possible_time)
-        .map(|next_target| TSPNode {
-            time: get_next_time_for(next_target),
-            target: next_target,
-            dist: dist + instance.dist_from_to(last_target, next_target),
-            visited_node_hash: calc_commutative_hash(node.data().visited_node_hash, next_target),
+        .filter(|next_target| {
+            soft_windows || get_next_time_for(*next_target) <= instance.window_of(*next_target).1
+        })
+        .filter(|next_target| instance.allows_city_at(*next_target, nr_visited))
+        .filter(|next_target| {
+            instance.predecessors_visited(*next_target, |city| visited.contains(city))
+        })
+        .filter(|next_target| {
+            nr_visited > 1 || settings.first_city.is_none_or(|c| c == *next_target)
+        })
+        .collect();
+
+    let children_towards = |targets: Vec<usize>| -> Vec<TSPNode> {
+        targets
+            .into_iter()
+            .map(|next_target| {
+                let mut child = current.visit(instance, next_target);
+                if is_complete_tour(&visited.with(next_target), next_target, instance) {
+                    if !settings.include_return_cost && !instance.is_open_tour() {
+                        child.dist = current.dist;
+                    }
+                    child.skipped = instance.len() - nr_visited;
+                    child.lower_bound = 0.0;
+                } else if let Some(min_incoming_edges) = settings.min_incoming_edges
+                    && !instance.is_optional(next_target)
+                {
+                    child.lower_bound -= min_incoming_edges[next_target];
+                }
+                child.cost = settings.cost_of(&child);
+                child
+            })
+            .filter(|child| {
+                settings.lookahead_depth == 0
+                    || has_feasible_continuation(child, visited, instance, settings)
+            })
+            .collect()
+    };
+
+    // Only the children towards a nearest neighbor or back to the depot are built, the others only if
+    // none of those is feasible.
+    let expanded_nodes = match settings.candidate_lists {
+        Some(candidate_lists) => {
+            let (near, far): (Vec<_>, Vec<_>) =
+                feasible_targets.into_iter().partition(|next_target| {
+                    *next_target == depot || candidate_lists[last_target].contains(next_target)
+                });
+            let near_children = children_towards(near);
+            if near_children.is_empty() {
+                children_towards(far)
+            } else {
+                near_children
+            }
+        }
+        None => children_towards(feasible_targets),
+    };
+
+    let mut children = match settings.random_candidates {
+        Some(nr_candidates) if expanded_nodes.len() > nr_candidates => {
+            sample_candidates(current, expanded_nodes, nr_candidates, settings.seed)
+        }
+        _ => expanded_nodes,
+    };
+    if let Some(seed) = settings.tie_break_seed {
+        children.shuffle(&mut node_rng(current, seed));
+    }
+    children
+}
+
+// The rng is seeded from the node itself, so that the random decisions do not depend on the order in
+// which the nodes are expanded in parallel.
+fn node_rng(current: &TSPNode, seed: u64) -> StdRng {
+    let node_seed = seed
+        ^ current.visited_node_hash.rotate_left(32)
+        ^ current.target as u64
+        ^ current.dist.to_bits();
+    StdRng::seed_from_u64(node_seed)
+}
+
+fn sample_candidates(
+    current: &TSPNode,
+    candidates: Vec<TSPNode>,
+    nr_candidates: usize,
+    seed: u64,
+) -> Vec<TSPNode> {
+    let mut rng = node_rng(current, seed);
+
+    let mut chosen = vec![false; candidates.len()];
+    for i in rand::seq::index::sample(&mut rng, candidates.len(), nr_candidates) {
+        chosen[i] = true;
+    }
+
+    candidates
+        .into_iter()
+        .zip(chosen)
+        .filter_map(|(candidate, is_chosen)| is_chosen.then_some(candidate))
+        .collect()
+}
+
+// A child is a dead end if it does not complete the tour and has no feasible successor within the next depth levels.
+fn has_feasible_continuation(
+    child: &TSPNode,
+    visited: &VisitedCities,
+    instance: &TSPInstance,
+    settings: &ExpansionSettings,
+) -> bool {
+    let visited_with_child = visited.with(child.target);
+
+    is_complete_tour(&visited_with_child, child.target, instance)
+        || !expand_from(
+            child,
+            &visited_with_child,
+            instance,
+            &ExpansionSettings {
+                lookahead_depth: settings.lookahead_depth - 1,
+                random_candidates: None,
+                ..*settings
+            },
+        )
+        .is_empty()
+}
+
+/// For every feasible successor of the node, how much extra distance it costs to postpone it by one step,
+/// i.e. to reach it via the best other successor instead of directly. Successors that become unreachable
+/// when postponed have infinite regret. Sorted by decreasing regret, so the most urgent city comes first.
+pub fn next_city_regret(instance: &TSPInstance, node: &Node<TSPNode>) -> Vec<(usize, f64)> {
+    let successors = expand(node, instance, &ExpansionSettings::default());
+
+    let mut regrets: Vec<(usize, f64)> = successors
+        .iter()
+        .map(|direct| {
+            let postponed = successors
+                .iter()
+                .filter(|other| other.target != direct.target && other.target != instance.depot())
+                .map(|other| (other, other.visit(instance, direct.target)))
+                .filter(|(other, via)| via.lateness == other.lateness)
+                .map(|(_, via)| via.dist)
+                .min_by(f64::total_cmp)
+                .unwrap_or(f64::INFINITY);
+            (direct.target, postponed - direct.dist)
         })
         .collect();
 
-    expanded_nodes
+    regrets.sort_by(|a, b| b.1.total_cmp(&a.1));
+    regrets
 }
 
-fn is_similar(a: &Node<TSPNode>, b: &Node<TSPNode>) -> bool {
+fn is_similar(a: &Node<TSPNode>, b: &Node<TSPNode>, instance: &TSPInstance) -> bool {
     if a.data().target != b.data().target {
         return false;
     }
+    if instance.len() <= u128::BITS as usize {
+        return a.data().visited_mask == b.data().visited_mask;
+    }
 
     let mut a_cities: Vec<_> = a.ancestors().map(|node| node.data().target).collect();
     let mut b_cities: Vec<_> = b.ancestors().map(|node| node.data().target).collect();
@@ -103,29 +499,243 @@ fn is_similar(a: &Node<TSPNode>, b: &Node<TSPNode>) -> bool {
     a_cities == b_cities
 }
 
-pub fn solve_tsp(instance: Arc<TSPInstance>, params: Params) -> Option<TSPSolution> {
-    let start_node = TSPNode {
-        time: instance.window_of(0).0,
-        target: 0,
-        dist: 0.0,
-        visited_node_hash: 1,
-    };
+// Same visited cities and last city, and not better in anything the objective or the feasibility depends on,
+// so no completion of the dominated node can beat the same completion of the other one.
+fn is_dominated_by(
+    dominated: &Node<TSPNode>,
+    other: &Node<TSPNode>,
+    instance: &TSPInstance,
+) -> bool {
+    let (a, b) = (dominated.data(), other.data());
+    a.time >= b.time
+        && a.dist >= b.dist
+        && a.wait_count >= b.wait_count
+        && a.lateness >= b.lateness
+        && is_similar(dominated, other, instance)
+}
+
+fn min_incoming_edges(instance: &TSPInstance) -> Vec<f64> {
+    (0..instance.len())
+        .map(|to| {
+            (0..instance.len())
+                .filter(|&from| from != to)
+                .map(|from| instance.dist_from_to(from, to))
+                .min_by(f64::total_cmp)
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
+fn candidate_lists(instance: &TSPInstance, size: usize) -> Vec<Vec<usize>> {
+    (0..instance.len())
+        .map(|from| {
+            let mut neighbors: Vec<usize> = (0..instance.len())
+                .filter(|&to| to != from && !instance.is_forbidden(from, to))
+                .collect();
+            neighbors.sort_by(|&a, &b| {
+                instance
+                    .dist_from_to(from, a)
+                    .total_cmp(&instance.dist_from_to(from, b))
+            });
+            neighbors.truncate(size);
+            neighbors
+        })
+        .collect()
+}
+
+fn run_beamsearch(
+    instance: &Arc<TSPInstance>,
+    params: Params,
+    observer: &mut dyn SolverObserver<TSPNode>,
+) -> Option<SolverResult<TSPNode>> {
+    run_beamsearch_from(instance, params, None, observer)
+}
+
+fn fails_precheck(instance: &TSPInstance, params: &Params) -> bool {
+    // with soft windows, only the precedences can make an instance infeasible
+    if let Err(reason) = instance.feasibility_precheck()
+        && (params.lateness_penalty.is_none() || reason == InfeasibilityReason::PrecedenceCycle)
+    {
+        warn!("Instance is infeasible, skipping the search: {reason}.");
+        return true;
+    }
+    false
+}
+
+// Runs the search once plus once per restart, where every restart breaks ties in the cost differently.
+fn run_beamsearch_from(
+    instance: &Arc<TSPInstance>,
+    params: Params,
+    first_city: Option<usize>,
+    observer: &mut dyn SolverObserver<TSPNode>,
+) -> Option<SolverResult<TSPNode>> {
+    if fails_precheck(instance, &params) {
+        return None;
+    }
+
+    let archive_size = params.archive_size;
+    let mut result = run_single_beamsearch(instance, params.clone(), first_city, None, observer);
+    for restart in 1..=params.restarts {
+        let tie_break_seed = params.seed.wrapping_add(restart as u64);
+        let restarted = run_single_beamsearch(
+            instance,
+            params.clone(),
+            first_city,
+            Some(tie_break_seed),
+            observer,
+        );
+        debug!(
+            "Restart {restart} found distance {:?}",
+            restarted.best.as_ref().map(|best| best.data().dist)
+        );
+        result = result.merge(restarted, archive_size);
+    }
+
+    Some(result)
+}
 
-    let result = BeamsearchSolver::new(
+fn run_single_beamsearch(
+    instance: &Arc<TSPInstance>,
+    params: Params,
+    first_city: Option<usize>,
+    tie_break_seed: Option<u64>,
+    observer: &mut dyn SolverObserver<TSPNode>,
+) -> SolverResult<TSPNode> {
+    let depot = instance.depot();
+    let mut start_node = TSPNode::start_at(instance, depot);
+
+    let mut settings = ExpansionSettings::from(&params);
+    settings.first_city = first_city;
+    settings.tie_break_seed = tie_break_seed;
+    let min_incoming_edges = min_incoming_edges(instance);
+    if params.completion_lower_bound {
+        settings.min_incoming_edges = Some(&min_incoming_edges);
+        // every mandatory city still has to be entered once, and so has the depot if the return counts
+        start_node.lower_bound = (0..instance.len())
+            .filter(|&city| city != depot && !instance.is_optional(city))
+            .chain((settings.include_return_cost && !instance.is_open_tour()).then_some(depot))
+            .map(|city| min_incoming_edges[city])
+            .sum();
+        start_node.cost = settings.cost_of(&start_node);
+    }
+    let candidate_lists = params
+        .candidate_list_size
+        .map(|size| candidate_lists(instance, size));
+    settings.candidate_lists = candidate_lists.as_deref();
+    let soft_windows = settings.lateness_penalty.is_some();
+    let checkpoint = params.checkpoint.clone();
+    let similarity_mode = params.similarity_mode;
+    let similarity_time_epsilon = params.similarity_time_epsilon;
+
+    let solver = BeamsearchSolver::new(
         vec![start_node],
-        |node| expand(node, &instance),
-        |x, y| x.data().target == y.data().target && (x.data().time - y.data().time).abs() < 1.0,
+        |node| expand(node, instance, &settings),
+        // the collection passes the worse node first
+        |x, y| match similarity_mode {
+            SimilarityMode::ArrivalTime => {
+                x.data().target == y.data().target
+                    && (x.data().time - y.data().time).abs() < similarity_time_epsilon
+            }
+            SimilarityMode::Dominance => is_dominated_by(x, y, instance),
+        },
         |n| n.data().visited_node_hash,
-        |n| make_tsp_solution_from_node(instance.clone(), n).is_valid(),
+        // only nodes back at the depot can be complete closed tours
+        |n| {
+            if !instance.is_open_tour() && n.data().target != depot {
+                return false;
+            }
+            let solution = make_tsp_solution_from_node(instance.clone(), n);
+            if soft_windows {
+                solution.is_complete()
+            } else {
+                solution.is_valid()
+            }
+        },
         params,
-    )
-    .solve();
+    );
 
-    if result.best.is_none() {
-        return None;
+    match checkpoint {
+        Some((path, trigger)) => solver.solve_with_observer(&mut CheckpointObserver {
+            instance: instance.clone(),
+            path,
+            trigger,
+            last_write: Instant::now(),
+            inner: observer,
+        }),
+        None => solver.solve_with_observer(observer),
+    }
+}
+
+/// Writes the distance and the path of the incumbent to a file whenever the trigger fires,
+/// and forwards every iteration to the inner observer.
+struct CheckpointObserver<'a> {
+    instance: Arc<TSPInstance>,
+    path: PathBuf,
+    trigger: CheckpointTrigger,
+    last_write: Instant,
+    inner: &'a mut dyn SolverObserver<TSPNode>,
+}
+
+impl CheckpointObserver<'_> {
+    fn is_due(&self, iteration: usize) -> bool {
+        match self.trigger {
+            CheckpointTrigger::Iterations(n) => n > 0 && iteration.is_multiple_of(n),
+            CheckpointTrigger::Interval(interval) => self.last_write.elapsed() >= interval,
+        }
+    }
+
+    fn write(&self, incumbent: &Node<TSPNode>) -> std::io::Result<()> {
+        let solution = make_tsp_solution_from_node(self.instance.clone(), incumbent);
+        let path: Vec<String> = solution.get_path().iter().map(usize::to_string).collect();
+        let content = format!(
+            "{}\n{}\n",
+            solution.get_time_distance().dist,
+            path.join(" ")
+        );
+        std::fs::write(&self.path, content)
+    }
+}
+
+impl SolverObserver<TSPNode> for CheckpointObserver<'_> {
+    fn on_iteration(&mut self, event: &IterationEvent<TSPNode>) {
+        self.inner.on_iteration(event);
+
+        if !self.is_due(event.iteration) {
+            return;
+        }
+        if let Some(incumbent) = event.incumbent {
+            // a failing checkpoint must not abort the solve
+            match self.write(incumbent) {
+                Ok(()) => self.last_write = Instant::now(),
+                Err(e) => warn!("Failed to write checkpoint {}: {e}", self.path.display()),
+            }
+        }
+    }
+}
+
+pub fn solve_tsp(instance: Arc<TSPInstance>, params: Params) -> Option<TSPSolution> {
+    let soft_windows = params.lateness_penalty.is_some();
+    let mut result = run_beamsearch(&instance, params.clone(), &mut NoObserver)?;
+
+    // doubles the beam width until a valid tour is found or the maximum width is exceeded
+    let mut beam_width = params.beam_width;
+    while result.best.is_none()
+        && let Some(max_width) = params.auto_widen
+        && beam_width < max_width
+    {
+        beam_width = (beam_width * 2).clamp(1, max_width);
+        info!("No valid tour found, retrying with beam width {beam_width}");
+        result = run_beamsearch(
+            &instance,
+            Params {
+                beam_width,
+                ..params.clone()
+            },
+            &mut NoObserver,
+        )?;
     }
 
-    let best_node = result.best.unwrap();
+    let best_node = result.best?;
     info!(
         "Found best result with distance {},  nr_expansions {} and time {}",
         &best_node.data().dist,
@@ -135,15 +745,290 @@ pub fn solve_tsp(instance: Arc<TSPInstance>, params: Params) -> Option<TSPSoluti
 
     let solution = make_tsp_solution_from_node(instance, &best_node);
 
-    debug!("Best solution: {:?}", solution.get_path());
-    assert!(solution.is_valid_subsolution());
+    trace!("Best solution: {:?}", solution.get_path());
+    assert!(soft_windows || solution.is_valid_subsolution());
 
     Some(solution)
 }
 
+/// Returns all archived tours that are not dominated in both distance and makespan, sorted by increasing
+/// distance. The archive is widened to the beam width, so that it holds every tour of the final beam.
+pub fn solve_tsp_pareto(instance: Arc<TSPInstance>, params: Params) -> Vec<TSPSolution> {
+    let params = Params {
+        archive_size: params.archive_size.max(params.beam_width),
+        ..params
+    };
+    let Some(result) = run_beamsearch(&instance, params, &mut NoObserver) else {
+        return Vec::new();
+    };
+
+    let mut valid_solutions: Vec<TSPSolution> = result
+        .archive
+        .iter()
+        .map(|node| make_tsp_solution_from_node(instance.clone(), node))
+        .collect();
+
+    valid_solutions.sort_by(|a, b| {
+        let a = a.get_time_distance();
+        let b = b.get_time_distance();
+        a.dist.total_cmp(&b.dist).then(a.time.total_cmp(&b.time))
+    });
+
+    let mut pareto_front = Vec::new();
+    let mut best_makespan = f64::INFINITY;
+    for sol in valid_solutions {
+        let makespan = sol.get_time_distance().time;
+        if makespan < best_makespan {
+            best_makespan = makespan;
+            pareto_front.push(sol);
+        }
+    }
+    pareto_front
+}
+
+/// Up to k distinct valid tours sorted by increasing distance. Like for `solve_tsp_pareto`, the archive is
+/// widened to the beam width, so that the alternatives include every valid tour of the final beam. Tours are
+/// compared by their canonical path, so on symmetric instances a reversed tour is no new alternative.
+pub fn solve_tsp_k(instance: Arc<TSPInstance>, params: Params, k: usize) -> Vec<TSPSolution> {
+    let params = Params {
+        archive_size: params.archive_size.max(params.beam_width).max(k),
+        ..params
+    };
+    let Some(result) = run_beamsearch(&instance, params, &mut NoObserver) else {
+        return Vec::new();
+    };
+
+    let mut solutions: Vec<TSPSolution> = result
+        .archive
+        .iter()
+        .map(|node| make_tsp_solution_from_node(instance.clone(), node))
+        .collect();
+    solutions.sort_by(|a, b| {
+        a.get_time_distance()
+            .dist
+            .total_cmp(&b.get_time_distance().dist)
+    });
+
+    let mut seen_paths = HashSet::new();
+    solutions.retain(|solution| seen_paths.insert(solution.canonical_path()));
+    solutions.truncate(k);
+    solutions
+}
+
+/// Best tours of independent searches that each leave the depot towards a different city.
+pub struct MultiStartResult {
+    /// The first city after the depot and the best tour found from there, in the order of the given starts.
+    pub per_start: Vec<(usize, Option<TSPSolution>)>,
+}
+
+impl MultiStartResult {
+    /// The shortest tour over all starts.
+    pub fn best(&self) -> Option<&TSPSolution> {
+        self.per_start
+            .iter()
+            .filter_map(|(_, solution)| solution.as_ref())
+            .min_by(|a, b| {
+                a.get_time_distance()
+                    .dist
+                    .total_cmp(&b.get_time_distance().dist)
+            })
+    }
+}
+
+/// Runs one beam search per start in parallel, each forced to visit its start city first. Unlike seeding a
+/// single beam with all starts, the starts do not compete for room in the beam.
+pub fn solve_tsp_multistart(
+    instance: Arc<TSPInstance>,
+    starts: &[usize],
+    params: Params,
+) -> MultiStartResult {
+    let per_start = starts
+        .par_iter()
+        .map(|&start| {
+            let best = run_beamsearch_from(&instance, params.clone(), Some(start), &mut NoObserver)
+                .and_then(|result| result.best)
+                .map(|best_node| make_tsp_solution_from_node(instance.clone(), &best_node));
+            (start, best)
+        })
+        .collect();
+
+    MultiStartResult { per_start }
+}
+
+/// Best tours of independent searches that each break ties and draw random decisions with a different seed.
+pub struct MultiSeedResult {
+    /// The seed and the best tour found with it, in the order of the given seeds. Tours that miss windows, as
+    /// found with a lateness penalty, are left out.
+    pub per_seed: Vec<(u64, Option<TSPSolution>)>,
+}
+
+impl MultiSeedResult {
+    /// The shortest tour over all seeds.
+    pub fn best(&self) -> Option<&TSPSolution> {
+        self.per_seed
+            .iter()
+            .filter_map(|(_, solution)| solution.as_ref())
+            .min_by(|a, b| {
+                a.get_time_distance()
+                    .dist
+                    .total_cmp(&b.get_time_distance().dist)
+            })
+    }
+
+    /// Number of seeds whose search found a valid tour.
+    pub fn nr_valid(&self) -> usize {
+        self.per_seed
+            .iter()
+            .filter(|(_, solution)| solution.is_some())
+            .count()
+    }
+}
+
+/// Runs one search per seed in parallel, each with the seed as `Params::seed` and as tie-breaker of equal costs.
+/// Several narrow searches can use the cores better than a single wide one, since they explore different ties.
+/// The searches are given by their seeds rather than by a number of starts, so that single starts can be
+/// reproduced; pass `0..n` for `n` starts. `Params::restarts` is ignored.
+pub fn solve_tsp_multiseed(
+    instance: Arc<TSPInstance>,
+    params: Params,
+    seeds: &[u64],
+) -> MultiSeedResult {
+    if fails_precheck(&instance, &params) {
+        return MultiSeedResult {
+            per_seed: seeds.iter().map(|&seed| (seed, None)).collect(),
+        };
+    }
+
+    let per_seed = seeds
+        .par_iter()
+        .map(|&seed| {
+            let params = Params {
+                seed,
+                ..params.clone()
+            };
+            let best = run_single_beamsearch(&instance, params, None, Some(seed), &mut NoObserver)
+                .best
+                .map(|best_node| make_tsp_solution_from_node(instance.clone(), &best_node))
+                .filter(TSPSolution::is_valid);
+            (seed, best)
+        })
+        .collect();
+
+    MultiSeedResult { per_seed }
+}
+
+/// Path and distance of every node kept in the beam, one entry per iteration.
+pub type FrontierSnapshots = Vec<Vec<(Vec<usize>, f64)>>;
+
+#[derive(Default)]
+struct FrontierSnapshotObserver {
+    snapshots: FrontierSnapshots,
+}
+
+impl SolverObserver<TSPNode> for FrontierSnapshotObserver {
+    fn on_iteration(&mut self, event: &IterationEvent<TSPNode>) {
+        let snapshot = event
+            .kept
+            .iter()
+            .map(|node| {
+                let path = node
+                    .path_from_root()
+                    .iter()
+                    .map(|data| data.target)
+                    .collect();
+                (path, node.data().fitness())
+            })
+            .collect();
+        self.snapshots.push(snapshot);
+    }
+}
+
+/// Like `solve_tsp`, but additionally records the beam after every iteration, e.g. for animations.
+/// Memory grows with beam width times number of cities squared, so only use it for small runs.
+pub fn solve_tsp_with_snapshots(
+    instance: Arc<TSPInstance>,
+    params: Params,
+) -> (Option<TSPSolution>, FrontierSnapshots) {
+    let mut observer = FrontierSnapshotObserver::default();
+    let best = run_beamsearch(&instance, params, &mut observer)
+        .and_then(|result| result.best)
+        .map(|best_node| make_tsp_solution_from_node(instance, &best_node));
+
+    (best, observer.snapshots)
+}
+
+/// Outcome of solving the same instance with and without `prune_similars`. Distances are None if no
+/// valid tour was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PruningImpact {
+    pub dist_with_pruning: Option<f64>,
+    pub dist_without_pruning: Option<f64>,
+    pub expansions_with_pruning: usize,
+    pub expansions_without_pruning: usize,
+}
+
+impl PruningImpact {
+    pub fn changed_result(&self) -> bool {
+        match (self.dist_with_pruning, self.dist_without_pruning) {
+            (Some(with), Some(without)) => (with - without).abs() > 1e-9,
+            (with, without) => with.is_some() != without.is_some(),
+        }
+    }
+}
+
+/// Solves twice with the given params, once with and once without similarity pruning.
+pub fn pruning_impact(instance: Arc<TSPInstance>, params: Params) -> PruningImpact {
+    let solve = |prune_similars| {
+        let result = run_beamsearch(
+            &instance,
+            Params {
+                prune_similars,
+                ..params.clone()
+            },
+            &mut NoObserver,
+        );
+        let dist = result
+            .as_ref()
+            .and_then(|result| result.best.as_ref())
+            .map(|best| {
+                make_tsp_solution_from_node(instance.clone(), best)
+                    .get_time_distance()
+                    .dist
+            });
+        (dist, result.map_or(0, |result| result.nr_expansions))
+    };
+
+    let (dist_with_pruning, expansions_with_pruning) = solve(true);
+    let (dist_without_pruning, expansions_without_pruning) = solve(false);
+
+    PruningImpact {
+        dist_with_pruning,
+        dist_without_pruning,
+        expansions_with_pruning,
+        expansions_without_pruning,
+    }
+}
+
+/// Finds the smallest beam width in `1..=max_width` for which `solve_tsp` with similarity pruning finds a valid
+/// solution. The widths are tried one after another, since a wider beam does not always succeed where a narrower
+/// one does, e.g. if its additional nodes push the only completable node out of the beam after pruning.
+pub fn minimal_feasible_width(instance: Arc<TSPInstance>, max_width: usize) -> Option<usize> {
+    (1..=max_width).find(|&beam_width| {
+        solve_tsp(
+            instance.clone(),
+            Params {
+                beam_width,
+                prune_similars: true,
+                ..Default::default()
+            },
+        )
+        .is_some()
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::super::tsp::TimeDist;
+    use super::super::super::tsp::{TimeDist, solve_tsp_exact};
     use super::*;
 
     fn create_test_instance() -> TSPInstance {
@@ -167,16 +1052,24 @@ mod tests {
         )
     }
 
+    fn create_greedy_trap_instance() -> TSPInstance {
+        // greedy (beam width 1) takes 0 -> 1 first, after which the window of 2 cannot be reached anymore
+        TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 10.0],
+                vec![1.0, 0.0, 10.0],
+                vec![1.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (0.0, 1000.0), (0.0, 10.0)],
+        )
+    }
+
     #[test]
     pub fn expander_works() {
         let instance = create_small_instance();
-        let node = Node::new_root(TSPNode {
-            time: 0.0,
-            target: 0,
-            dist: 0.0,
-            visited_node_hash: 0,
-        });
-        let expanded = expand(&node, &instance);
+        let node = Node::new_root(TSPNode::start_at(&instance, 0));
+        let expanded = expand(&node, &instance, &ExpansionSettings::default());
 
         assert_eq!(expanded.len(), 1);
         let node = &expanded[0];
@@ -205,13 +1098,8 @@ mod tests {
                 (0.0, 4000.0),
             ],
         );
-        let node = Node::new_root(TSPNode {
-            time: 0.0,
-            target: 0,
-            dist: 0.0,
-            visited_node_hash: 0,
-        });
-        let expanded = expand(&node, &instance);
+        let node = Node::new_root(TSPNode::start_at(&instance, 0));
+        let expanded = expand(&node, &instance, &ExpansionSettings::default());
 
         assert_eq!(expanded.len(), 1);
         let node = &expanded[0];
@@ -229,6 +1117,7 @@ mod tests {
             Params {
                 beam_width: 100,
                 prune_similars: true,
+                ..Default::default()
             },
         );
 
@@ -247,4 +1136,1090 @@ mod tests {
         );
         assert!(sol.is_valid());
     }
+
+    #[test]
+    pub fn minimal_feasible_width_needs_two_if_greedy_choice_fails() {
+        let instance = create_greedy_trap_instance();
+
+        assert_eq!(minimal_feasible_width(Arc::new(instance), 10), Some(2));
+    }
+
+    #[test]
+    pub fn minimal_feasible_width_is_none_if_max_width_is_too_small() {
+        let instance = create_greedy_trap_instance();
+
+        assert_eq!(minimal_feasible_width(Arc::new(instance), 1), None);
+    }
+
+    #[test]
+    pub fn lookahead_prunes_dead_end_children() {
+        // 0 -> 1 is feasible itself, but from 1 neither 2 nor 3 can be reached before 2 closes at time 20
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 5.0, 10.0, 10.0],
+                vec![5.0, 0.0, 30.0, 30.0],
+                vec![10.0, 10.0, 0.0, 10.0],
+                vec![10.0, 10.0, 5.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (0.0, 100.0), (0.0, 20.0), (0.0, 1000.0)],
+        );
+        let node = Node::new_root(TSPNode::start_at(&instance, 0));
+
+        let without_lookahead: Vec<usize> = expand(&node, &instance, &ExpansionSettings::default())
+            .iter()
+            .map(|n| n.target)
+            .collect();
+        let with_lookahead: Vec<usize> = expand(
+            &node,
+            &instance,
+            &ExpansionSettings {
+                lookahead_depth: 1,
+                ..Default::default()
+            },
+        )
+        .iter()
+        .map(|n| n.target)
+        .collect();
+
+        assert_eq!(without_lookahead, vec![1, 2, 3]);
+        assert_eq!(with_lookahead, vec![2, 3]);
+    }
+
+    #[test]
+    pub fn initial_hash_makes_visited_hash_independent_of_visiting_order() {
+        let instance = TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 1.0],
+                vec![1.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 100.0)],
+        );
+        let root = Node::new_root(TSPNode::start_at(&instance, 0));
+
+        let hashes_after_two_steps: Vec<u64> =
+            expand(&root, &instance, &ExpansionSettings::default())
+                .into_iter()
+                .map(|child| root.new_child(child))
+                .flat_map(|child| expand(&child, &instance, &ExpansionSettings::default()))
+                .map(|grandchild| grandchild.visited_node_hash)
+                .collect();
+
+        // 0 -> 1 -> 2 and 0 -> 2 -> 1
+        assert_eq!(hashes_after_two_steps.len(), 2);
+        assert_eq!(hashes_after_two_steps[0], hashes_after_two_steps[1]);
+        assert_eq!(
+            hashes_after_two_steps[0],
+            calc_commutative_hash_64(calc_commutative_hash_64(INITIAL_HASH_64, 1), 2)
+        );
+    }
+
+    #[test]
+    pub fn pareto_returns_distance_makespan_tradeoff() {
+        // 0 -> 1 -> 2 -> 0 is shorter (11 vs 25) but has to wait at 1 longer, finishing at 110 instead of 105
+        let instance = TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 10.0],
+                vec![5.0, 0.0, 5.0],
+                vec![5.0, 10.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (100.0, 1000.0), (0.0, 1000.0)],
+        );
+
+        let front = solve_tsp_pareto(
+            Arc::new(instance),
+            Params {
+                beam_width: 10,
+                ..Default::default()
+            },
+        );
+
+        let paths: Vec<&Vec<usize>> = front.iter().map(|sol| sol.get_path()).collect();
+        assert_eq!(paths, vec![&vec![0, 1, 2, 0], &vec![0, 2, 1, 0]]);
+        assert_eq!(
+            front[0].get_time_distance(),
+            TimeDist {
+                time: 110.0,
+                dist: 11.0
+            }
+        );
+        assert_eq!(
+            front[1].get_time_distance(),
+            TimeDist {
+                time: 105.0,
+                dist: 25.0
+            }
+        );
+    }
+
+    #[test]
+    pub fn pinned_city_stays_at_its_position() {
+        // unpinned, the cheapest tour is 0 -> 1 -> 2 -> 3 -> 0
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 1.0, 10.0, 10.0],
+                vec![10.0, 0.0, 1.0, 10.0],
+                vec![10.0, 10.0, 0.0, 1.0],
+                vec![1.0, 10.0, 10.0, 0.0],
+            ],
+            vec![(0.0, 1000.0); 4],
+        )
+        .with_pinned_positions(vec![(2, 3)]);
+        let instance = Arc::new(instance);
+
+        let params = || Params {
+            beam_width: 100,
+            ..Default::default()
+        };
+        let best = solve_tsp(instance.clone(), params()).unwrap();
+        let front = solve_tsp_pareto(instance, params());
+
+        assert_eq!(best.get_path()[2], 3);
+        assert!(!front.is_empty());
+        assert!(front.iter().all(|sol| sol.get_path()[2] == 3));
+    }
+
+    #[test]
+    pub fn build_node_chain_matches_solution() {
+        let instance = Arc::new(create_test_instance());
+        let path = vec![0, 2, 1, 0];
+
+        let node = build_node_chain(&instance, &path);
+        let sol = TSPSolution::new(instance.clone(), path);
+
+        assert_eq!(
+            TimeDist {
+                time: node.data().time,
+                dist: node.data().dist
+            },
+            sol.get_time_distance()
+        );
+        assert_eq!(node.data().visited_node_hash, sol.visited_hash());
+        assert_eq!(
+            *make_tsp_solution_from_node(instance, &node).get_path(),
+            vec![0, 2, 1, 0]
+        );
+    }
+
+    #[test]
+    pub fn ignoring_return_cost_changes_optimal_tour() {
+        // 0 -> 1 -> 2 is cheapest (2) but returning from 2 costs 100, so with return 0 -> 2 -> 1 -> 0 (5) wins
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 2.0],
+                vec![1.0, 0.0, 1.0],
+                vec![100.0, 2.0, 0.0],
+            ],
+            vec![(0.0, 1000.0); 3],
+        ));
+
+        let with_return = solve_tsp(
+            instance.clone(),
+            Params {
+                beam_width: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let without_return = solve_tsp(
+            instance,
+            Params {
+                beam_width: 10,
+                include_return_cost: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(*with_return.get_path(), vec![0, 2, 1, 0]);
+        assert_eq!(*without_return.get_path(), vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    pub fn frontier_snapshots_are_taken_every_iteration() {
+        let (best, snapshots) = solve_tsp_with_snapshots(
+            Arc::new(create_test_instance()),
+            Params {
+                beam_width: 1,
+                ..Default::default()
+            },
+        );
+
+        // two cities and the return to the depot
+        assert_eq!(snapshots.len(), 3);
+        assert!(snapshots.iter().all(|snapshot| snapshot.len() <= 1));
+        assert_eq!(snapshots[0][0].0.len(), 2);
+        assert_eq!(snapshots[2][0].0, *best.unwrap().get_path());
+    }
+
+    #[test]
+    pub fn random_candidates_are_reproducible_per_seed() {
+        let instance = Arc::new(TSPInstance::new(
+            6,
+            vec![vec![1.0; 6]; 6],
+            vec![(0.0, 1000.0); 6],
+        ));
+        let solve_with_seed = |seed| {
+            solve_tsp(
+                instance.clone(),
+                Params {
+                    beam_width: 10,
+                    random_candidates: Some(1),
+                    seed,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .get_path()
+            .clone()
+        };
+
+        assert_eq!(solve_with_seed(7), solve_with_seed(7));
+
+        let tours: HashSet<Vec<usize>> = (0..10).map(solve_with_seed).collect();
+        assert!(tours.len() > 1);
+    }
+
+    #[test]
+    pub fn wait_count_weight_prefers_tour_without_waiting() {
+        // 0 -> 1 -> 2 -> 0 is shorter (3 vs 15), but has to wait at 1 which opens at 10
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 5.0],
+                vec![5.0, 0.0, 1.0],
+                vec![1.0, 5.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (10.0, 1000.0), (0.0, 1000.0)],
+        ));
+
+        let by_distance = solve_tsp(
+            instance.clone(),
+            Params {
+                beam_width: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let by_waits = solve_tsp(
+            instance,
+            Params {
+                beam_width: 10,
+                wait_count_weight: 100.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(*by_distance.get_path(), vec![0, 1, 2, 0]);
+        assert_eq!(*by_waits.get_path(), vec![0, 2, 1, 0]);
+    }
+
+    #[test]
+    pub fn expensive_optional_city_is_skipped_for_small_penalty() {
+        let instance = Arc::new(
+            TSPInstance::new(
+                3,
+                vec![
+                    vec![0.0, 1.0, 50.0],
+                    vec![1.0, 0.0, 50.0],
+                    vec![50.0, 50.0, 0.0],
+                ],
+                vec![(0.0, 1000.0); 3],
+            )
+            .with_optional_cities(vec![false, false, true]),
+        );
+        let solve_with_penalty = |skip_penalty| {
+            solve_tsp(
+                instance.clone(),
+                Params {
+                    beam_width: 10,
+                    skip_penalty,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let skipping = solve_with_penalty(10.0);
+        let visiting = solve_with_penalty(1000.0);
+
+        assert_eq!(*skipping.get_path(), vec![0, 1, 0]);
+        assert_eq!(skipping.skipped_cities(), vec![2]);
+        assert!(skipping.is_valid());
+        assert_eq!(visiting.skipped_cities(), Vec::<usize>::new());
+        let penalized = skipping.get_time_distance().dist + 10.0;
+        assert!(penalized < visiting.get_time_distance().dist);
+    }
+
+    #[test]
+    pub fn optional_city_with_missed_window_is_skipped_not_visited_late() {
+        // 2 is 5 away from the depot, but its window closes at 1
+        let instance = Arc::new(
+            TSPInstance::new(
+                3,
+                vec![
+                    vec![0.0, 1.0, 5.0],
+                    vec![1.0, 0.0, 5.0],
+                    vec![5.0, 5.0, 0.0],
+                ],
+                vec![(0.0, 1000.0), (0.0, 1000.0), (0.0, 1.0)],
+            )
+            .with_optional_cities(vec![false, false, true]),
+        );
+        let root = TSPNode::start_at(&instance, 0);
+
+        let children: Vec<usize> = expand(
+            &Node::new_root(root),
+            &instance,
+            &ExpansionSettings::default(),
+        )
+        .iter()
+        .map(|n| n.target)
+        .collect();
+        let sol = solve_tsp(
+            instance,
+            Params {
+                beam_width: 10,
+                skip_penalty: 1000.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(children, vec![1]);
+        assert_eq!(*sol.get_path(), vec![0, 1, 0]);
+        assert!(sol.is_valid());
+    }
+
+    #[test]
+    pub fn small_lateness_penalty_accepts_late_but_short_tour() {
+        // 0 -> 1 -> 2 -> 0 costs 3 but reaches 2 at time 2, half a unit after its window closed
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 1.0],
+                vec![10.0, 0.0, 1.0],
+                vec![1.0, 10.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (0.0, 1000.0), (0.0, 1.5)],
+        ));
+        let solve_with_penalty = |lateness_penalty| {
+            solve_tsp(
+                instance.clone(),
+                Params {
+                    beam_width: 10,
+                    lateness_penalty,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let hard = solve_with_penalty(None);
+        let soft = solve_with_penalty(Some(1.0));
+
+        assert_eq!(*hard.get_path(), vec![0, 2, 1, 0]);
+        assert_eq!(hard.get_time_distance().dist, 21.0);
+        assert_eq!(*soft.get_path(), vec![0, 1, 2, 0]);
+        assert_eq!(soft.get_time_distance().dist, 3.0);
+        assert_eq!(soft.total_lateness(), 0.5);
+        assert!(!soft.is_valid());
+    }
+
+    #[test]
+    pub fn city_with_closing_window_has_highest_regret() {
+        // city 3 closes at 2.5, so it can only be reached in time directly from the depot
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 1.0, 2.0, 1.0],
+                vec![1.0, 0.0, 1.0, 2.0],
+                vec![2.0, 1.0, 0.0, 2.0],
+                vec![1.0, 2.0, 2.0, 0.0],
+            ],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 100.0), (0.0, 2.5)],
+        );
+        let root = Node::new_root(TSPNode::start_at(&instance, 0));
+
+        let regrets = next_city_regret(&instance, &root);
+
+        assert_eq!(regrets.len(), 3);
+        assert_eq!(regrets[0], (3, f64::INFINITY));
+        assert!(regrets[1..].iter().all(|(_, regret)| regret.is_finite()));
+    }
+
+    #[test]
+    pub fn visited_mask_matches_ancestors() {
+        let instance = create_test_instance();
+        let a = build_node_chain(&instance, &[0, 1, 2]);
+        let b = build_node_chain(&instance, &[0, 2, 1, 2]);
+
+        assert_eq!(a.data().visited_mask, 0b111);
+        assert!(is_similar(&a, &b, &instance));
+
+        let mask = VisitedCities::of(&a, &instance);
+        let list = VisitedCities::List(vec![0, 1, 2]);
+        for visited in [&mask, &list] {
+            assert_eq!(visited.len(), 3);
+            assert!((0..3).all(|city| visited.contains(city)));
+            assert!(!visited.contains(3));
+            assert!(visited.with(3).contains(3));
+            assert_eq!(visited.with(3).len(), 4);
+            assert_eq!(visited.unvisited(instance.len()), Vec::<usize>::new());
+            assert_eq!(visited.unvisited(6), vec![3, 4, 5]);
+        }
+    }
+
+    // run with `cargo test --release bench_expand -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_expand_on_large_instance() {
+        let instance =
+            TSPInstance::from_file("instances/SolomonPotvinBengio/rc_204.1.txt".into()).unwrap();
+        let half: Vec<usize> = (0..instance.len() / 2).collect();
+        let node = build_node_chain(&instance, &half);
+        let settings = ExpansionSettings::default();
+
+        for (name, visited) in [
+            ("mask", VisitedCities::of(&node, &instance)),
+            ("list", VisitedCities::List(half.clone())),
+        ] {
+            let start = Instant::now();
+            for _ in 0..100_000 {
+                std::hint::black_box(expand_from(node.data(), &visited, &instance, &settings));
+            }
+            println!("{} cities, {name}: {:?}", instance.len(), start.elapsed());
+        }
+    }
+
+    #[test]
+    pub fn checkpoint_file_contains_incumbent() {
+        let path =
+            std::env::temp_dir().join(format!("rsopt_checkpoint_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let result = solve_tsp(
+            Arc::new(create_test_instance()),
+            Params {
+                beam_width: 100,
+                checkpoint: Some((path.clone(), CheckpointTrigger::Iterations(1))),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(*result.get_path(), vec![0, 2, 1, 0]);
+        assert_eq!(content.unwrap(), "1101\n0 2 1 0\n");
+    }
+
+    #[test]
+    pub fn pruning_keeps_optimum_of_test_instance() {
+        let impact = pruning_impact(
+            Arc::new(create_test_instance()),
+            Params {
+                beam_width: 100,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(impact.dist_with_pruning, Some(1101.0));
+        assert_eq!(impact.dist_without_pruning, Some(1101.0));
+        assert!(!impact.changed_result());
+        assert!(impact.expansions_with_pruning <= impact.expansions_without_pruning);
+    }
+
+    #[test]
+    pub fn completion_lower_bound_avoids_cheap_first_step() {
+        // 0 -> 1 is the cheapest first step, but then 2 has to be entered via an edge of at least 50
+        // and left via one of 100, while 0 -> 2 -> 1 -> 0 costs 51.5
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 50.0],
+                vec![1.0, 0.0, 100.0],
+                vec![100.0, 0.5, 0.0],
+            ],
+            vec![(0.0, 1000.0); 3],
+        ));
+        let solve_greedy = |completion_lower_bound| {
+            solve_tsp(
+                instance.clone(),
+                Params {
+                    beam_width: 1,
+                    completion_lower_bound,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .get_time_distance()
+            .dist
+        };
+
+        assert_eq!(solve_greedy(false), 201.0);
+        assert_eq!(solve_greedy(true), 51.5);
+
+        let result = solve_tsp(
+            Arc::new(create_test_instance()),
+            Params {
+                completion_lower_bound: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(*result.get_path(), vec![0, 2, 1, 0]);
+    }
+
+    #[test]
+    pub fn multistart_reports_best_of_every_start() {
+        // around the unit square from 1 the tour has length 4, starting with the diagonal to 2 it is longer
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0); 4],
+        ));
+
+        let result = solve_tsp_multistart(
+            instance,
+            &[1, 2],
+            Params {
+                beam_width: 10,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(result.per_start.len(), 2);
+        let (start, from_1) = &result.per_start[0];
+        assert_eq!(*start, 1);
+        assert_eq!(from_1.as_ref().unwrap().get_time_distance().dist, 4.0);
+        let (start, from_2) = &result.per_start[1];
+        assert_eq!(*start, 2);
+        let from_2 = from_2.as_ref().unwrap();
+        assert_eq!(from_2.get_path()[1], 2);
+        assert!((from_2.get_time_distance().dist - (2.0 + 2.0 * 2f64.sqrt())).abs() < 1e-9);
+
+        assert_eq!(
+            *result.best().unwrap().get_path(),
+            *from_1.as_ref().unwrap().get_path()
+        );
+    }
+
+    #[test]
+    pub fn multiseed_reports_best_of_every_seed() {
+        let instance = Arc::new(TSPInstance::random(12, 100.0, 0.3, 4));
+        let params = Params {
+            beam_width: 2,
+            ..Default::default()
+        };
+        let seeds: Vec<u64> = (0..8).collect();
+
+        let result = solve_tsp_multiseed(instance.clone(), params.clone(), &seeds);
+
+        assert_eq!(
+            result
+                .per_seed
+                .iter()
+                .map(|(seed, _)| *seed)
+                .collect::<Vec<_>>(),
+            seeds
+        );
+        assert!(result.nr_valid() > 0);
+        let best = result.best().unwrap();
+        assert!(best.is_valid());
+        assert!(result.per_seed.iter().all(|(_, solution)| {
+            solution.as_ref().is_none_or(|solution| {
+                best.get_time_distance().dist <= solution.get_time_distance().dist
+            })
+        }));
+        let again = solve_tsp_multiseed(instance, params, &seeds);
+        assert_eq!(again.best().unwrap().get_path(), best.get_path());
+    }
+
+    #[test]
+    pub fn multiseed_finds_nothing_on_infeasible_instance() {
+        // 1 has to be reached by time 1 and 2 by time 2, but they are 2 apart
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        ));
+
+        let result = solve_tsp_multiseed(instance, Params::default(), &[1, 2, 3]);
+
+        assert_eq!(result.per_seed.len(), 3);
+        assert_eq!(result.nr_valid(), 0);
+        assert!(result.best().is_none());
+    }
+
+    #[test]
+    pub fn multiseed_does_not_count_late_tours_as_valid() {
+        // every tour is late, but with soft windows the searches still complete one
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (-1.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 1.0), (0.0, 2.0)],
+        ));
+        let params = Params {
+            lateness_penalty: Some(1.0),
+            ..Default::default()
+        };
+
+        let result = solve_tsp_multiseed(instance, params, &[1, 2, 3]);
+
+        assert_eq!(result.nr_valid(), 0);
+        assert!(result.best().is_none());
+    }
+
+    #[test]
+    pub fn precedence_forces_longer_route() {
+        // without the precedence, the cheapest tour is 0 -> 1 -> 2 -> 3 -> 0
+        let instance = Arc::new(
+            TSPInstance::new(
+                4,
+                vec![
+                    vec![0.0, 1.0, 10.0, 10.0],
+                    vec![10.0, 0.0, 1.0, 10.0],
+                    vec![10.0, 10.0, 0.0, 1.0],
+                    vec![1.0, 10.0, 10.0, 0.0],
+                ],
+                vec![(0.0, 1000.0); 4],
+            )
+            .with_precedences(vec![(3, 2)]),
+        );
+
+        let best = solve_tsp(
+            instance,
+            Params {
+                beam_width: 100,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let path = best.get_path();
+        let position_of = |city| path.iter().position(|&c| c == city).unwrap();
+        assert!(position_of(3) < position_of(2));
+        assert!(best.get_time_distance().dist > 4.0);
+        assert!(best.is_valid());
+    }
+
+    #[test]
+    pub fn tour_starts_and_ends_at_chosen_depot() {
+        // the only cheap tour is the cycle 0 -> 1 -> 2 -> 3 -> 0, entered wherever the depot is
+        let solve_with_depot = |depot| {
+            let instance = TSPInstance::new(
+                4,
+                vec![
+                    vec![0.0, 1.0, 10.0, 10.0],
+                    vec![10.0, 0.0, 1.0, 10.0],
+                    vec![10.0, 10.0, 0.0, 1.0],
+                    vec![1.0, 10.0, 10.0, 0.0],
+                ],
+                vec![(0.0, 1000.0); 4],
+            )
+            .with_depot(depot);
+            solve_tsp(
+                Arc::new(instance),
+                Params {
+                    beam_width: 100,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let from_0 = solve_with_depot(0);
+        let from_2 = solve_with_depot(2);
+
+        assert_eq!(*from_0.get_path(), vec![0, 1, 2, 3, 0]);
+        assert_eq!(*from_2.get_path(), vec![2, 3, 0, 1, 2]);
+        assert!(from_2.is_valid());
+        assert_eq!(from_2.get_time_distance().dist, 4.0);
+    }
+
+    #[test]
+    pub fn top_k_returns_distinct_tours_sorted_by_distance() {
+        // the 3 tours around the unit square up to direction: along the border, and 2 with the diagonals
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0); 4],
+        ));
+        let params = Params {
+            prune_similars: false,
+            ..Default::default()
+        };
+
+        let solutions = solve_tsp_k(instance, params, 4);
+
+        let distances: Vec<f64> = solutions
+            .iter()
+            .map(|s| s.get_time_distance().dist)
+            .collect();
+        let with_diagonals = 2.0 + 2.0 * 2f64.sqrt();
+        assert_eq!(distances, vec![4.0, with_diagonals, with_diagonals]);
+        let paths: HashSet<Vec<usize>> = solutions.iter().map(|s| s.canonical_path()).collect();
+        assert_eq!(paths.len(), 3);
+        assert!(solutions.iter().all(TSPSolution::is_valid));
+    }
+
+    #[test]
+    pub fn restarts_break_ties_differently() {
+        // both first steps cost 1, but after 0 -> 1 the window of 2 is missed; the unperturbed width 1
+        // beam keeps the first of the tied children. Similarity pruning is off, since it reorders the beam
+        // by hash groups.
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 1.0],
+                vec![1.0, 0.0, 1.0],
+                vec![1.0, 1.0, 0.0],
+            ],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 1.5)],
+        ));
+        let params = |restarts| Params {
+            beam_width: 1,
+            prune_similars: false,
+            restarts,
+            ..Default::default()
+        };
+
+        assert!(solve_tsp(instance.clone(), params(0)).is_none());
+        let restarted = solve_tsp(instance, params(10)).unwrap();
+
+        assert_eq!(*restarted.get_path(), vec![0, 2, 1, 0]);
+    }
+
+    #[test]
+    pub fn auto_widen_retries_until_tour_is_found() {
+        let instance = Arc::new(create_greedy_trap_instance());
+        let params = |auto_widen| Params {
+            beam_width: 1,
+            auto_widen,
+            ..Default::default()
+        };
+
+        assert!(solve_tsp(instance.clone(), params(None)).is_none());
+        assert!(solve_tsp(instance.clone(), params(Some(1))).is_none());
+        let widened = solve_tsp(instance, params(Some(8))).unwrap();
+
+        assert!(widened.is_valid());
+    }
+
+    #[test]
+    pub fn random_instances_are_feasible() {
+        for seed in 0..5 {
+            let instance = Arc::new(TSPInstance::random(12, 100.0, 0.95, seed));
+
+            let solution = solve_tsp(
+                instance,
+                Params {
+                    beam_width: 1000,
+                    ..Default::default()
+                },
+            );
+
+            assert!(solution.is_some_and(|solution| solution.is_valid()));
+        }
+    }
+
+    #[test]
+    pub fn dominated_node_is_detected_despite_large_time_difference() {
+        // both visited all cities on the line and end at 3, but going back and forth takes 2 longer
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (5.0, 0.0)],
+            vec![(0.0, 1000.0); 4],
+        );
+        let better = build_node_chain(&instance, &[0, 1, 2, 3]);
+        let worse = build_node_chain(&instance, &[0, 2, 1, 3]);
+
+        assert!(worse.data().time - better.data().time >= 1.0);
+        assert!(is_dominated_by(&worse, &better, &instance));
+        assert!(!is_dominated_by(&better, &worse, &instance));
+    }
+
+    #[test]
+    pub fn dominance_pruning_keeps_optimum() {
+        let instance = Arc::new(TSPInstance::random(8, 100.0, 0.5, 1));
+        let solve = |prune_similars, similarity_mode| {
+            let result = run_beamsearch(
+                &instance,
+                Params {
+                    beam_width: 1_000_000,
+                    prune_similars,
+                    similarity_mode,
+                    ..Default::default()
+                },
+                &mut NoObserver,
+            )
+            .unwrap();
+            (result.best.unwrap().data().dist, result.nr_expansions)
+        };
+
+        let (exact_dist, exact_expansions) = solve(false, SimilarityMode::default());
+        let (dominance_dist, dominance_expansions) = solve(true, SimilarityMode::Dominance);
+
+        assert!((exact_dist - dominance_dist).abs() < 1e-9);
+        assert!(dominance_expansions < exact_expansions);
+    }
+
+    #[test]
+    pub fn obviously_infeasible_instance_skips_search() {
+        // city 2 is 5 away from the depot but closes at 3
+        let instance = Arc::new(TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (5.0, 0.0), (2.0, 0.0)],
+            vec![(0.0, 100.0), (0.0, 100.0), (0.0, 3.0), (0.0, 100.0)],
+        ));
+
+        assert!(run_beamsearch(&instance, Params::default(), &mut NoObserver).is_none());
+        assert!(solve_tsp(instance.clone(), Params::default()).is_none());
+        // with soft windows, being late is allowed
+        let soft = Params {
+            lateness_penalty: Some(1.0),
+            ..Default::default()
+        };
+        assert!(solve_tsp(instance, soft).is_some());
+    }
+
+    #[test]
+    pub fn full_candidate_lists_keep_optimum() {
+        for seed in 0..3 {
+            let instance = Arc::new(TSPInstance::random(8, 100.0, 0.5, seed));
+            let Some(exact) = solve_tsp_exact(&instance) else {
+                continue;
+            };
+
+            let solution = solve_tsp(
+                instance.clone(),
+                Params {
+                    beam_width: 1_000_000,
+                    similarity_mode: SimilarityMode::Dominance,
+                    candidate_list_size: Some(instance.len()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert!(
+                (solution.get_time_distance().dist - exact.get_time_distance().dist).abs() < 1e-9
+            );
+        }
+    }
+
+    #[test]
+    pub fn short_candidate_lists_expand_fewer_nodes() {
+        let instance = Arc::new(TSPInstance::random(8, 100.0, 0.5, 1));
+        let solve = |candidate_list_size| {
+            run_beamsearch(
+                &instance,
+                Params {
+                    beam_width: 1_000_000,
+                    candidate_list_size,
+                    ..Default::default()
+                },
+                &mut NoObserver,
+            )
+            .unwrap()
+        };
+
+        let all = solve(None);
+        let nearest = solve(Some(2));
+
+        assert!(nearest.nr_expansions < all.nr_expansions);
+        let best = nearest.best.unwrap();
+        assert!(make_tsp_solution_from_node(instance.clone(), &best).is_valid());
+    }
+
+    #[test]
+    pub fn candidate_lists_fall_back_to_all_feasible_cities() {
+        // from 1, only 2 is near, but 2 may only follow 3
+        let instance = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.5, 0.0), (10.0, 0.0)],
+            vec![(0.0, 1000.0); 4],
+        )
+        .with_precedences(vec![(3, 2)]);
+        let candidate_lists = candidate_lists(&instance, 1);
+        let settings = ExpansionSettings {
+            candidate_lists: Some(&candidate_lists),
+            ..Default::default()
+        };
+
+        let children = expand(&build_node_chain(&instance, &[0, 1]), &instance, &settings);
+
+        assert_eq!(candidate_lists[1], vec![2]);
+        assert_eq!(
+            children
+                .iter()
+                .map(|child| child.target)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    pub fn candidate_lists_fall_back_if_all_near_children_are_dead_ends() {
+        // 1 is nearest to 0, but from 1 neither 2 nor 3 can be reached before 2 closes at time 20
+        let instance = TSPInstance::new(
+            4,
+            vec![
+                vec![0.0, 5.0, 10.0, 10.0],
+                vec![5.0, 0.0, 30.0, 30.0],
+                vec![10.0, 10.0, 0.0, 10.0],
+                vec![10.0, 10.0, 5.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (0.0, 100.0), (0.0, 20.0), (0.0, 1000.0)],
+        );
+        let candidate_lists = candidate_lists(&instance, 1);
+        let expand_with_lookahead = |lookahead_depth| {
+            let settings = ExpansionSettings {
+                candidate_lists: Some(&candidate_lists),
+                lookahead_depth,
+                ..Default::default()
+            };
+            expand(&build_node_chain(&instance, &[0]), &instance, &settings)
+                .iter()
+                .map(|child| child.target)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(candidate_lists[0], vec![1]);
+        assert_eq!(expand_with_lookahead(0), vec![1]);
+        assert_eq!(expand_with_lookahead(1), vec![2, 3]);
+    }
+
+    #[test]
+    pub fn larger_similarity_epsilon_prunes_more() {
+        let instance = Arc::new(TSPInstance::random(8, 100.0, 0.5, 1));
+        let solve = |similarity_time_epsilon| {
+            run_beamsearch(
+                &instance,
+                Params {
+                    beam_width: 1_000_000,
+                    similarity_time_epsilon,
+                    ..Default::default()
+                },
+                &mut NoObserver,
+            )
+            .unwrap()
+            .nr_expansions
+        };
+
+        let unpruned = solve(0.0);
+        let default_pruning = solve(1.0);
+        let coarse_pruning = solve(1000.0);
+
+        assert!(default_pruning < unpruned);
+        assert!(coarse_pruning < default_pruning);
+    }
+
+    #[test]
+    pub fn makespan_objective_prefers_tour_that_waits_less() {
+        // 0 -> 1 -> 2 -> 0 is short, but waits at 1 until 100 before two long edges; the long way
+        // round reaches 1 last and ends right after its window opens
+        let instance = Arc::new(TSPInstance::new(
+            3,
+            vec![
+                vec![0.0, 1.0, 20.0],
+                vec![1.0, 0.0, 5.0],
+                vec![5.0, 5.0, 0.0],
+            ],
+            vec![(0.0, 1000.0), (100.0, 1000.0), (0.0, 1000.0)],
+        ));
+        let solve = |objective| {
+            solve_tsp(
+                instance.clone(),
+                Params {
+                    objective,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let shortest = solve(Objective::MinDistance);
+        let fastest = solve(Objective::MinMakespan);
+
+        assert_eq!(*shortest.get_path(), vec![0, 1, 2, 0]);
+        assert_eq!(
+            shortest.get_time_distance(),
+            TimeDist {
+                time: 110.0,
+                dist: 11.0
+            }
+        );
+        assert_eq!(*fastest.get_path(), vec![0, 2, 1, 0]);
+        assert_eq!(
+            fastest.get_time_distance(),
+            TimeDist {
+                time: 101.0,
+                dist: 26.0
+            }
+        );
+    }
+
+    #[test]
+    pub fn solver_routes_around_forbidden_edge() {
+        // without the edge between 1 and 2, no tour can follow the border of the square
+        let square = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            vec![(0.0, 1000.0); 4],
+        );
+        let mut distances: Vec<Vec<f64>> = (0..4)
+            .map(|from| (0..4).map(|to| square.dist_from_to(from, to)).collect())
+            .collect();
+        distances[1][2] = f64::INFINITY;
+        distances[2][1] = f64::INFINITY;
+        let instance = Arc::new(TSPInstance::new(4, distances, vec![(0.0, 1000.0); 4]));
+
+        for lateness_penalty in [None, Some(1.0)] {
+            let solution = solve_tsp(
+                instance.clone(),
+                Params {
+                    lateness_penalty,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            assert!(solution.is_valid());
+            assert!(
+                solution
+                    .get_path()
+                    .windows(2)
+                    .all(|edge| !instance.is_forbidden(edge[0], edge[1]))
+            );
+            assert!((solution.get_time_distance().dist - (2.0 + 2.0 * 2f64.sqrt())).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    pub fn open_tour_is_shorter_than_closed_tour() {
+        // cities on a line: the closed tour has to come all the way back from 4
+        let closed = TSPInstance::from_coordinates(
+            vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0), (4.0, 0.0)],
+            vec![(0.0, 1000.0); 5],
+        );
+        let open = Arc::new(
+            TSPInstance::from_coordinates(
+                vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0), (4.0, 0.0)],
+                vec![(0.0, 1000.0); 5],
+            )
+            .with_open_tour(true),
+        );
+
+        let closed_solution = solve_tsp(Arc::new(closed), Params::default()).unwrap();
+        let open_solution = solve_tsp(open.clone(), Params::default()).unwrap();
+
+        assert_eq!(closed_solution.get_time_distance().dist, 8.0);
+        assert_eq!(*open_solution.get_path(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(open_solution.get_time_distance().dist, 4.0);
+        assert!(open_solution.is_valid());
+        assert_eq!(
+            solve_tsp_exact(&open).unwrap().get_time_distance().dist,
+            4.0
+        );
+    }
 }
@@ -6,12 +6,12 @@ pub const fn is_prime(n: u32) -> bool {
     if n <= 3 {
         return true;
     }
-    if n % 2 == 0 || n % 3 == 0 {
+    if n.is_multiple_of(2) || n.is_multiple_of(3) {
         return false;
     }
     let mut i: u64 = 5;
     while i * i <= n as u64 {
-        if n as u64 % i == 0 || (n as u64) % (i + 2) == 0 {
+        if (n as u64).is_multiple_of(i) || (n as u64).is_multiple_of(i + 2) {
             return false;
         }
         i += 6;
@@ -22,7 +22,7 @@ pub const fn is_prime(n: u32) -> bool {
 pub const fn calc_next_prime_not_above(n: u32) -> u32 {
     assert!(n >= 3);
     let mut candidate = n;
-    if candidate % 2 == 0 {
+    if candidate.is_multiple_of(2) {
         candidate -= 1;
     }
     while !is_prime(candidate) {
@@ -31,10 +31,15 @@ pub const fn calc_next_prime_not_above(n: u32) -> u32 {
     candidate
 }
 
+/// Hash of a path that has only visited the depot, used as seed for the start node.
+/// Each further city multiplies the hash by (city + 1) modulo a prime, so nodes which visited the same set
+/// of cities share a hash regardless of order. It must not be 0, since 0 would absorb every multiplication.
+pub const INITIAL_HASH: u32 = 1;
+
 pub const fn calc_commutative_hash(mut seed: u32, node: usize) -> u32 {
     // since Z_n is a field for prime n, we will never reach 0 except if we start with 0
     if seed == 0 {
-        seed += 1;
+        seed = INITIAL_HASH;
     }
     // 1000 is the number of cities we expect to handle at most
     // it it's more, the hash will still work, but less efficiently with more collisions
@@ -42,6 +47,125 @@ pub const fn calc_commutative_hash(mut seed: u32, node: usize) -> u32 {
     seed.wrapping_mul((node + 1) as u32) % PRIME
 }
 
+/// Like `INITIAL_HASH`, for `calc_commutative_hash_64`.
+pub const INITIAL_HASH_64: u64 = 1;
+
+/// Like `calc_commutative_hash`, but modulo the largest prime below 2^64, so that collisions stay rare even for
+/// instances with many thousands of cities.
+pub const fn calc_commutative_hash_64(mut seed: u64, node: usize) -> u64 {
+    if seed == 0 {
+        seed = INITIAL_HASH_64;
+    }
+    // 2^64 - 59; the product is taken in 128 bits, so that the multiplication is exact before the modulo
+    const PRIME: u64 = u64::MAX - 58;
+    ((seed as u128 * (node as u128 + 1)) % PRIME as u128) as u64
+}
+
+/// Hungarian algorithm (with potentials, O(n^3)) for the square linear assignment problem.
+/// Returns the minimal total cost of assigning each row to a distinct column.
+pub fn min_cost_assignment(cost: &[Vec<f64>]) -> f64 {
+    let n = cost.len();
+    // rows and columns are 1-based, index 0 is a virtual column to start augmenting paths from
+    let mut row_potential = vec![0.0; n + 1];
+    let mut col_potential = vec![0.0; n + 1];
+    let mut row_of_col = vec![0usize; n + 1];
+    let mut predecessor = vec![0usize; n + 1];
+
+    for row in 1..=n {
+        row_of_col[0] = row;
+        let mut col = 0;
+        let mut min_reduced = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[col] = true;
+            let current_row = row_of_col[col];
+            let mut delta = f64::INFINITY;
+            let mut next_col = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced =
+                    cost[current_row - 1][j - 1] - row_potential[current_row] - col_potential[j];
+                if reduced < min_reduced[j] {
+                    min_reduced[j] = reduced;
+                    predecessor[j] = col;
+                }
+                if min_reduced[j] < delta {
+                    delta = min_reduced[j];
+                    next_col = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    row_potential[row_of_col[j]] += delta;
+                    col_potential[j] -= delta;
+                } else {
+                    min_reduced[j] -= delta;
+                }
+            }
+            col = next_col;
+            if row_of_col[col] == 0 {
+                break;
+            }
+        }
+
+        while col != 0 {
+            let previous = predecessor[col];
+            row_of_col[col] = row_of_col[previous];
+            col = previous;
+        }
+    }
+
+    (1..=n).map(|j| cost[row_of_col[j] - 1][j - 1]).sum()
+}
+
+/// Cyclic Jacobi rotations for a symmetric matrix. Returns the eigenvalues together with the eigenvectors,
+/// where column i of the returned matrix belongs to eigenvalue i.
+pub fn symmetric_eigen(matrix: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut vectors: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // rotation angle chosen such that a[p][q] becomes zero
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut().chain(vectors.iter_mut()) {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (upper, lower) = a.split_at_mut(q);
+                for (pk, qk) in upper[p].iter_mut().zip(lower[0].iter_mut()) {
+                    (*pk, *qk) = (c * *pk - s * *qk, s * *pk + c * *qk);
+                }
+            }
+        }
+    }
+
+    ((0..n).map(|i| a[i][i]).collect(), vectors)
+}
+
 #[cfg(test)]
 mod tests {
     use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
@@ -111,4 +235,41 @@ mod tests {
             assert_eq!(hash_backward, hash_shuffled);
         }
     }
+
+    #[test]
+    pub fn test_calc_commutative_hash_64_is_never_zero() {
+        let mut hash = INITIAL_HASH_64;
+        for i in 0..1000 {
+            hash = calc_commutative_hash_64(hash, i);
+            assert!(hash != 0);
+        }
+    }
+
+    #[test]
+    pub fn test_calc_commutative_hash_64_is_commutative() {
+        let mut hash_forward = INITIAL_HASH_64;
+        for i in 1..1000 {
+            hash_forward = calc_commutative_hash_64(hash_forward, i);
+        }
+
+        let mut hash_backward = INITIAL_HASH_64;
+        for i in (1..1000).rev() {
+            hash_backward = calc_commutative_hash_64(hash_backward, i);
+        }
+        assert_eq!(hash_forward, hash_backward);
+
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let mut indices = (0..1000).collect::<Vec<usize>>();
+
+        for _ in 1..10 {
+            indices.shuffle(&mut rng);
+
+            let mut hash_shuffled = 0;
+            for node in &indices {
+                hash_shuffled = calc_commutative_hash_64(hash_shuffled, *node);
+            }
+            assert_eq!(hash_backward, hash_shuffled);
+        }
+    }
 }